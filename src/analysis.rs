@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::graph;
+use super::object::RoomObject;
+use super::rms;
+use super::rms::{Direction, Room, RoomIndex, Tile};
+use super::validate;
+
+/// A byte in the room record that nobody has figured out yet.
+pub struct UnknownField {
+    pub name: &'static str,
+    pub offset: usize,
+    pub get: fn(&Room) -> u8,
+    pub set: fn(&mut Room, u8),
+}
+
+pub const UNKNOWN_FIELDS: [UnknownField; 4] = [
+    UnknownField {
+        name: "unknown_a",
+        offset: rms::ROOM_RECORD_UNKNOWN_A_OFFSET,
+        get: |room| room.unknown_a,
+        set: |room, value| room.unknown_a = value,
+    },
+    UnknownField {
+        name: "unknown_b",
+        offset: rms::ROOM_RECORD_UNKNOWN_B_OFFSET,
+        get: |room| room.unknown_b,
+        set: |room, value| room.unknown_b = value,
+    },
+    UnknownField {
+        name: "unknown_c",
+        offset: rms::ROOM_RECORD_UNKNOWN_C_OFFSET,
+        get: |room| room.unknown_c,
+        set: |room, value| room.unknown_c = value,
+    },
+    UnknownField {
+        name: "unknown_d",
+        offset: rms::ROOM_RECORD_UNKNOWN_D_OFFSET,
+        get: |room| room.unknown_d,
+        set: |room, value| room.unknown_d = value,
+    },
+];
+
+/// Everything we know about the rooms that hold one particular value of an unknown field.
+pub struct ValueReport {
+    pub value: u8,
+    pub rooms: Vec<RoomIndex>,
+    /// Average number of moves from the starting room, for the rooms that can be reached
+    pub mean_depth: Option<f64>,
+    /// The monster id most of these rooms use
+    pub common_monster_id: u8,
+}
+
+/// Histogram and correlations for one unknown field.
+pub struct FieldReport {
+    pub name: &'static str,
+    pub offset: usize,
+    /// Sorted by value
+    pub values: Vec<ValueReport>,
+    /// Pearson correlation against each known property of a room. None if either side never
+    /// changes, which makes correlation meaningless.
+    pub correlations: Vec<(&'static str, Option<f64>)>,
+}
+
+/// Room properties that the unknowns are compared against. `depth` and `floor` are None if the
+/// room can't be reached from the starting room.
+fn room_properties(
+    room: &Room,
+    depth: Option<usize>,
+    floor: Option<i32>,
+) -> Vec<(&'static str, Option<f64>)> {
+    let cells = (0..rms::ROOM_HEIGHT).flat_map(|y| (0..rms::ROOM_WIDTH).map(move |x| (x, y)));
+    let traps = cells
+        .clone()
+        .filter(|&(x, y)| matches!(room.get_tile(x, y), Tile::Trap(_)))
+        .count();
+    let objects = cells
+        .clone()
+        .filter(|&(x, y)| room.get_object(x, y) != RoomObject::None)
+        .count();
+    let chests = cells
+        .filter(|&(x, y)| room.get_object(x, y) == RoomObject::TreasureChest)
+        .count();
+    let exits = Direction::ALL
+        .iter()
+        .filter(|&&direction| room.link(direction).is_some())
+        .count();
+
+    let mut properties = vec![
+        ("monster id", Some(room.monster_id as f64)),
+        ("monster count", Some(room.monster_count as f64)),
+        ("spawn points", Some(room.monster_spawns().len() as f64)),
+        ("traps", Some(traps as f64)),
+        ("objects", Some(objects as f64)),
+        ("treasure chests", Some(chests as f64)),
+        ("exits", Some(exits as f64)),
+        (
+            "has ladder up",
+            Some(room.link(Direction::Up).is_some() as u8 as f64),
+        ),
+        (
+            "has ladder down",
+            Some(room.link(Direction::Down).is_some() as u8 as f64),
+        ),
+        ("depth", depth.map(|depth| depth as f64)),
+        ("floor", floor.map(|floor| floor as f64)),
+    ];
+    for field in UNKNOWN_FIELDS.iter() {
+        properties.push((field.name, Some((field.get)(room) as f64)));
+    }
+    properties
+}
+
+/// Pearson correlation coefficient of the pairs, None if either side has no variance.
+fn correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.is_empty() {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = pairs.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance_x: f64 = pairs.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let variance_y: f64 = pairs.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_x * variance_y).sqrt())
+}
+
+/// Build a report for every unknown field in the record.
+pub fn analyze_unknowns(rooms: &[Room]) -> Vec<FieldReport> {
+    let depths = graph::distances_from(rooms, validate::STARTING_ROOM);
+    let floors = graph::floors_from(rooms, validate::STARTING_ROOM);
+    let properties: Vec<_> = rooms
+        .iter()
+        .enumerate()
+        .map(|(i, room)| room_properties(room, depths[i], floors[i]))
+        .collect();
+
+    UNKNOWN_FIELDS
+        .iter()
+        .map(|field| {
+            let mut by_value: BTreeMap<u8, Vec<RoomIndex>> = BTreeMap::new();
+            for (i, room) in rooms.iter().enumerate() {
+                by_value.entry((field.get)(room)).or_default().push(i);
+            }
+            let values = by_value
+                .into_iter()
+                .map(|(value, value_rooms)| {
+                    let value_depths: Vec<usize> =
+                        value_rooms.iter().filter_map(|&i| depths[i]).collect();
+                    let mut monster_ids: BTreeMap<u8, usize> = BTreeMap::new();
+                    for &i in value_rooms.iter() {
+                        *monster_ids.entry(rooms[i].monster_id).or_default() += 1;
+                    }
+                    ValueReport {
+                        value,
+                        mean_depth: if value_depths.is_empty() {
+                            None
+                        } else {
+                            Some(
+                                value_depths.iter().sum::<usize>() as f64
+                                    / value_depths.len() as f64,
+                            )
+                        },
+                        // Ties go to the lowest id
+                        common_monster_id: monster_ids
+                            .iter()
+                            .max_by_key(|&(id, count)| (count, std::cmp::Reverse(id)))
+                            .map_or(0, |(&id, _)| id),
+                        rooms: value_rooms,
+                    }
+                })
+                .collect();
+
+            let correlations = match properties.first() {
+                None => vec![],
+                Some(first) => first
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (name, _))| *name != field.name)
+                    .map(|(p, (name, _))| {
+                        let pairs: Vec<(f64, f64)> = rooms
+                            .iter()
+                            .zip(properties.iter())
+                            .filter_map(|(room, room_properties)| {
+                                room_properties[p]
+                                    .1
+                                    .map(|property| ((field.get)(room) as f64, property))
+                            })
+                            .collect();
+                        (*name, correlation(&pairs))
+                    })
+                    .collect(),
+            };
+
+            FieldReport {
+                name: field.name,
+                offset: field.offset,
+                values,
+                correlations,
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for FieldReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} (offset {:#05X})", self.name, self.offset)?;
+        writeln!(f, "  value  rooms  mean depth  usual monster  room numbers")?;
+        for value in self.values.iter() {
+            let room_numbers: Vec<String> = value
+                .rooms
+                .iter()
+                .map(|room| (room + 1).to_string())
+                .collect();
+            writeln!(
+                f,
+                "  {:#04X}   {:>5}  {:>10}  {:>13}  {}",
+                value.value,
+                value.rooms.len(),
+                value
+                    .mean_depth
+                    .map_or(String::from("-"), |depth| format!("{:.1}", depth)),
+                value.common_monster_id,
+                room_numbers.join(" ")
+            )?;
+        }
+        writeln!(f, "  correlation with")?;
+        for (name, correlation) in self.correlations.iter() {
+            match correlation {
+                Some(correlation) => writeln!(f, "    {:<16} {:+.2}", name, correlation)?,
+                None => writeln!(f, "    {:<16} -", name)?,
+            }
+        }
+        Ok(())
+    }
+}
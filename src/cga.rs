@@ -1,5 +1,7 @@
 use super::crumb;
 use super::crumb::crumb;
+use super::error;
+use super::error::Error;
 use super::img;
 
 pub const CGA_HEADER: [u8; 4] = [0x0E, 0x00, 0x0E, 0x00];
@@ -22,19 +24,28 @@ const CGA_PALETTE: [img::Color; 4] = [
     img::Color::rgb(0xFF, 0xFF, 0xFF),
 ];
 
-pub fn load_spritesheet(filename: &str) -> Vec<img::Image> {
-    let pic_data = std::fs::read(filename).unwrap();
-    return pic_data
+pub fn load_spritesheet(filename: &str) -> error::Result<Vec<img::Image>> {
+    let pic_data = std::fs::read(filename).map_err(|e| Error::io(filename, e))?;
+    pic_data
         // Divide the stream of bytes into discrete image sections.
         .chunks(IMAGE_ALIGNMENT)
-        // TODO: Validate and throw away chunks that don't match expected size
+        .enumerate()
         // Ignore first row (CGA_HEADER, 4 bytes) and garbage after image data.
         // (IDK why CGA data is only 1/4 of the allotted space... ask John Murphy)
-        .map(|x| &x[IMAGE_ROW_SIZE..CGA_IMAGE_SIZE])
-        // TODO: Validate and throw away chunks that don't match expected size
+        .map(|(i, x)| {
+            x.get(IMAGE_ROW_SIZE..CGA_IMAGE_SIZE)
+                .ok_or_else(|| Error::TruncatedRecord {
+                    filename: filename.to_string(),
+                    record: i,
+                    offset: i * IMAGE_ALIGNMENT,
+                    expected: CGA_IMAGE_SIZE,
+                    actual: x.len(),
+                })
+        })
         // Turn byte chunks into images
         .map(|x| {
-            img::Image {
+            let x = x?;
+            Ok(img::Image {
                 width: img::IMAGE_DIMENSION_USIZE,
                 height: img::IMAGE_DIMENSION_USIZE,
                 pixels: x
@@ -47,7 +58,7 @@ pub fn load_spritesheet(filename: &str) -> Vec<img::Image> {
                     // Draw pixels
                     .map(|(_, x)| CGA_PALETTE[x as usize])
                     .collect(),
-            }
+            })
         })
-        .collect();
+        .collect()
 }
@@ -0,0 +1,722 @@
+use std::path::Path;
+
+use super::analysis;
+use super::diff;
+use super::error;
+use super::error::Error;
+use super::generate;
+use super::graph;
+use super::monster;
+use super::object::RoomObject;
+use super::patch::{Conflict, Patch};
+use super::png;
+use super::query;
+use super::randomize;
+use super::replace;
+use super::replace::{Region, Replacement, Scope};
+use super::rms;
+use super::rms_json;
+use super::rms_text;
+use super::sheet;
+use super::sprites;
+use super::stats;
+use super::text;
+use super::validate;
+use super::walk;
+
+const USAGE: &str = "\
+usage: rmsedit                      open DUNGEON.RMS in the editor
+       rmsedit check [FILE.RMS [PYMON.DAT]]
+                                    report broken links and other problems
+       rmsedit unknowns [FILE.RMS]  report how the undocumented bytes are used
+       rmsedit stats [--csv] [FILE.RMS]
+                                    count tiles, objects, monsters, traps and exits
+       rmsedit route FILE.RMS FROM TO [TO...]
+                                    the shortest way between rooms, through each TO in turn
+       rmsedit distances [FILE.RMS] how many moves every room is from the entrance
+       rmsedit walk FILE.RMS N      show where the player can walk in room N
+       rmsedit diff [--json] OLD.RMS NEW.RMS
+                                    report what changed in every room
+       rmsedit export-json FILE.RMS FILE.JSON
+       rmsedit import-json FILE.JSON FILE.RMS
+       rmsedit export-text FILE.RMS FILE.TXT
+                                    write rooms as text for version control
+       rmsedit import-text FILE.TXT FILE.RMS
+       rmsedit export-room FILE.RMS N FILE.ROOM
+                                    save room N on its own for sharing
+       rmsedit import-room [--links] FILE.ROOM FILE.RMS [N]
+                                    replace room N, or add a room at the end
+       rmsedit copy-room [--links] FROM.RMS N TO.RMS [M]
+                                    copy a room into another dungeon the same way
+       rmsedit find FILE.RMS QUERY...
+                                    list the rooms matching a query, like
+                                    'object \"chest\" and spawns = 0'
+       rmsedit replace [--dry-run] [--rooms 1,3-5] [--region X1,Y1,X2,Y2] FILE.RMS
+                       tile|object|monster FROM TO
+                                    replace everywhere, listing how much changed in each room
+       rmsedit generate [--floors N] [--rooms N] [--loops 0.2] [--curve 1.0] [--treasure 0.5]
+                        [--seed N] FILE.RMS [PYMON.DAT]
+                                    make a new dungeon with N rooms on each floor
+       rmsedit randomize [--seed N] [--tolerance 1.0] [--keep monsters,spawns,chests,objects]
+                         FILE.RMS [OUT.RMS]
+                                    shuffle monsters, spawn points, chests and objects
+       rmsedit contact-sheet [FILE.RMS] FILE.PNG
+                                    draw every room into one image
+       rmsedit patch-create ORIGINAL_DIR MODDED_DIR FILE.PATCH
+                                    describe a mod without including the original files
+       rmsedit patch-check FILE.PATCH DIR
+       rmsedit patch-apply FILE.PATCH DIR
+       rmsedit patch-revert FILE.PATCH DIR";
+
+const DEFAULT_RMS: &str = "DUNGEON.RMS";
+
+/// Rooms across a contact sheet. Four 300 pixel rooms fit comfortably on a screen.
+const CONTACT_SHEET_COLUMNS: usize = 4;
+
+/// Run a command line command instead of the editor, for scripting. `args` doesn't include the
+/// program name. Returns the exit code.
+pub fn run(args: &[String]) -> error::Result<i32> {
+    let arg = |i: usize| args.get(i).map(String::as_str);
+    match arg(0) {
+        Some("check") => check(arg(1).unwrap_or(DEFAULT_RMS), arg(2)),
+        Some("unknowns") => unknowns(arg(1).unwrap_or(DEFAULT_RMS)),
+        Some("stats") if arg(1) == Some("--csv") => stats(arg(2).unwrap_or(DEFAULT_RMS), true),
+        Some("stats") => stats(arg(1).unwrap_or(DEFAULT_RMS), false),
+        Some("route") if args.len() >= 4 => {
+            match args[2..].iter().map(|arg| room_number(arg)).collect() {
+                Some(numbers) => route(&args[1], numbers),
+                None => usage(),
+            }
+        }
+        Some("distances") => distances(arg(1).unwrap_or(DEFAULT_RMS)),
+        Some("walk") if args.len() == 3 => match room_number(&args[2]) {
+            Some(number) => walk(&args[1], number),
+            None => usage(),
+        },
+        Some("diff") if args.len() == 3 => diff(&args[1], &args[2], false),
+        Some("diff") if args.len() == 4 && args[1] == "--json" => diff(&args[2], &args[3], true),
+        Some("export-json") if args.len() == 3 => export_json(&args[1], &args[2]),
+        Some("import-json") if args.len() == 3 => import_json(&args[1], &args[2]),
+        Some("export-text") if args.len() == 3 => export_text(&args[1], &args[2]),
+        Some("import-text") if args.len() == 3 => import_text(&args[1], &args[2]),
+        Some("export-room") if args.len() == 4 => match room_number(&args[2]) {
+            Some(number) => export_room(&args[1], number, &args[3]),
+            None => usage(),
+        },
+        Some("import-room") => import_room(&args[1..], false),
+        Some("copy-room") => import_room(&args[1..], true),
+        Some("find") if args.len() >= 3 => find(&args[1], &args[2..].join(" ")),
+        Some("replace") => replace(&args[1..]),
+        Some("generate") => generate(&args[1..]),
+        Some("randomize") => randomize(&args[1..]),
+        Some("contact-sheet") if args.len() == 2 => contact_sheet(DEFAULT_RMS, &args[1]),
+        Some("contact-sheet") if args.len() == 3 => contact_sheet(&args[1], &args[2]),
+        Some("patch-create") if args.len() == 4 => patch_create(&args[1], &args[2], &args[3]),
+        Some("patch-check") if args.len() == 3 => {
+            let patch = load_patch(&args[1])?;
+            report_conflicts(patch.check(Path::new(&args[2]))?)
+        }
+        Some("patch-apply") if args.len() == 3 => {
+            let patch = load_patch(&args[1])?;
+            report_conflicts(patch.apply(Path::new(&args[2]))?)
+        }
+        Some("patch-revert") if args.len() == 3 => {
+            let patch = load_patch(&args[1])?.reversed();
+            report_conflicts(patch.apply(Path::new(&args[2]))?)
+        }
+        _ => usage(),
+    }
+}
+
+fn usage() -> error::Result<i32> {
+    eprintln!("{}", USAGE);
+    Ok(2)
+}
+
+/// A 1-based room number from the command line.
+fn room_number(arg: &str) -> Option<usize> {
+    arg.parse().ok().filter(|&number| number > 0)
+}
+
+/// Print every issue, failing if any of them are errors. Monsters are only checked if a monster
+/// list is given.
+fn check(filename: &str, monsters_filename: Option<&str>) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    let mut issues = validate::validate(&dungeon.rooms);
+    issues.extend(validate::validate_walkability(&dungeon.rooms));
+    issues.extend(validate::validate_edges(&dungeon.rooms));
+    if let Some(monsters_filename) = monsters_filename {
+        let monsters = monster::load_monsters(monsters_filename)?;
+        issues.extend(validate::validate_monsters(&dungeon.rooms, &monsters));
+    }
+    issues.sort_by_key(|issue| issue.room);
+    for issue in &issues {
+        println!("{}: {}", filename, issue);
+    }
+    Ok(if validate::has_errors(&issues) { 1 } else { 0 })
+}
+
+/// Print the reverse engineering report for the unknown bytes of every room.
+fn unknowns(filename: &str) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    for report in analysis::analyze_unknowns(&dungeon.rooms) {
+        println!("{}", report);
+    }
+    Ok(0)
+}
+
+fn stats(filename: &str, csv: bool) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    let statistics = stats::statistics(&dungeon.rooms, validate::STARTING_ROOM);
+    if csv {
+        print!("{}", statistics.to_csv());
+    } else {
+        print!("{}", statistics);
+    }
+    Ok(0)
+}
+
+/// Print the moves between each pair of rooms in `numbers` (1-based), failing if any pair isn't
+/// connected.
+fn route(filename: &str, numbers: Vec<usize>) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    if let Some(&number) = numbers.iter().find(|&&number| number > dungeon.rooms.len()) {
+        return Err(Error::Format {
+            filename: filename.to_string(),
+            message: format!("there's no room {}", number),
+        });
+    }
+    let mut total = 0;
+    for leg in numbers.windows(2) {
+        let (from, to) = (leg[0] - 1, leg[1] - 1);
+        match graph::route(&dungeon.rooms, from, to) {
+            Some(moves) => {
+                let through: Vec<String> = moves
+                    .iter()
+                    .map(|(_, room)| (room + 1).to_string())
+                    .collect();
+                println!(
+                    "room {} to room {}: {} moves",
+                    from + 1,
+                    to + 1,
+                    moves.len()
+                );
+                println!("  {}", graph::describe_route(&moves));
+                println!("  through rooms {}", through.join(" "));
+                total += moves.len();
+            }
+            None => {
+                println!("room {} to room {}: no way through", from + 1, to + 1);
+                return Ok(1);
+            }
+        }
+    }
+    if numbers.len() > 2 {
+        println!("{} moves in all", total);
+    }
+    Ok(0)
+}
+
+fn distances(filename: &str) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    let distances = graph::distances_from(&dungeon.rooms, validate::STARTING_ROOM);
+    for (i, distance) in distances.iter().enumerate() {
+        match distance {
+            Some(distance) => {
+                println!("room {} \"{}\": {}", i + 1, dungeon.rooms[i].name, distance)
+            }
+            None => println!("room {} \"{}\": unreachable", i + 1, dungeon.rooms[i].name),
+        }
+    }
+    Ok(0)
+}
+
+/// Print room `number` (1-based) as its passability, with cells that can't be reached from its
+/// exits blanked out, then which exits connect. Fails if the player could get stuck.
+fn walk(filename: &str, number: usize) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(filename)?;
+    let room = match dungeon.rooms.get(number - 1) {
+        Some(room) => room,
+        None => {
+            return Err(Error::Format {
+                filename: filename.to_string(),
+                message: format!("there's no room {}", number),
+            })
+        }
+    };
+    let walkability = walk::optimistic_walkability(room);
+    println!("room {} \"{}\"", number, room.name);
+    for y in 0..rms::ROOM_HEIGHT {
+        let row: String = (0..rms::ROOM_WIDTH)
+            .map(|x| {
+                let passability = walk::cell_passability(room, x, y);
+                if passability.is_passable() && !walkability.is_reachable(x, y) {
+                    ' '
+                } else {
+                    passability.character()
+                }
+            })
+            .collect();
+        println!("  {}", row);
+    }
+    let legend: Vec<String> = walk::Passability::ALL
+        .iter()
+        .map(|passability| format!("{} {}", passability.character(), passability.name()))
+        .collect();
+    println!(
+        "  ({}, blank can't be reached; unknown tiles are taken to be floor)",
+        legend.join(", ")
+    );
+
+    let exits: Vec<rms::Direction> = rms::Direction::ALL[..4]
+        .iter()
+        .copied()
+        .filter(|&direction| room.link(direction).is_some())
+        .collect();
+    for &from in exits.iter() {
+        let to: Vec<&str> = exits
+            .iter()
+            .filter(|&&to| to != from && walkability.connects(from, to))
+            .map(|to| to.name())
+            .collect();
+        if walkability.openings(from).is_empty() {
+            println!("{} exit: probably walled off", from.name());
+        } else if to.is_empty() {
+            println!("{} exit: leads nowhere else", from.name());
+        } else {
+            println!("{} exit: leads to {}", from.name(), to.join(", "));
+        }
+    }
+    for &(x, y) in walkability.unreachable_spots.iter() {
+        println!(
+            "{} at {},{} probably can't be reached",
+            room.get_object(x, y).name(),
+            x,
+            y
+        );
+    }
+
+    let issues = validate::validate_walkability(&dungeon.rooms[number - 1..number]);
+    Ok(if issues.is_empty() { 0 } else { 1 })
+}
+
+/// Print the differences as text or JSON. Like diff(1), fails if there are any.
+fn diff(old_filename: &str, new_filename: &str, json: bool) -> error::Result<i32> {
+    let old = rms::load_rooms(old_filename)?;
+    let new = rms::load_rooms(new_filename)?;
+    let differences = diff::diff(&old, &new);
+    if json {
+        print!("{}", differences.to_json());
+    } else {
+        print!("{}", differences);
+    }
+    Ok(if differences.is_empty() { 0 } else { 1 })
+}
+
+fn export_json(rms_filename: &str, json_filename: &str) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(rms_filename)?;
+    std::fs::write(json_filename, rms_json::to_json(&dungeon))
+        .map_err(|e| Error::io(json_filename, e))?;
+    Ok(0)
+}
+
+fn import_json(json_filename: &str, rms_filename: &str) -> error::Result<i32> {
+    let text = std::fs::read_to_string(json_filename).map_err(|e| Error::io(json_filename, e))?;
+    let dungeon = rms_json::from_json(json_filename, &text)?;
+    rms::save_rooms(&dungeon, rms_filename)?;
+    Ok(0)
+}
+
+fn export_text(rms_filename: &str, text_filename: &str) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(rms_filename)?;
+    std::fs::write(text_filename, rms_text::to_text(&dungeon))
+        .map_err(|e| Error::io(text_filename, e))?;
+    Ok(0)
+}
+
+fn import_text(text_filename: &str, rms_filename: &str) -> error::Result<i32> {
+    let text = std::fs::read_to_string(text_filename).map_err(|e| Error::io(text_filename, e))?;
+    let dungeon = rms_text::from_text(text_filename, &text)?;
+    rms::save_rooms(&dungeon, rms_filename)?;
+    Ok(0)
+}
+
+/// Room `number` (1-based) of an RMS file.
+fn load_room(rms_filename: &str, number: usize) -> error::Result<rms::Room> {
+    let mut dungeon = rms::load_rooms(rms_filename)?;
+    if number > dungeon.rooms.len() {
+        return Err(Error::Format {
+            filename: rms_filename.to_string(),
+            message: format!("there's no room {}", number),
+        });
+    }
+    Ok(dungeon.rooms.swap_remove(number - 1))
+}
+
+fn export_room(rms_filename: &str, number: usize, room_filename: &str) -> error::Result<i32> {
+    let room = load_room(rms_filename, number)?;
+    std::fs::write(room_filename, rms_text::room_to_text(number, &room))
+        .map_err(|e| Error::io(room_filename, e))?;
+    Ok(0)
+}
+
+/// import-room and copy-room, which only differ in where the room comes from. `args` starts after
+/// the command.
+fn import_room(args: &[String], from_rms: bool) -> error::Result<i32> {
+    // Exits are only copied when asked for, since they rarely mean anything in another dungeon
+    let with_links = args.first().map(String::as_str) == Some("--links");
+    let args = &args[with_links as usize..];
+    // copy-room has a room number after the file it reads from
+    let destination = if from_rms { 2 } else { 1 };
+    let number = match args.len() {
+        len if len == destination + 1 => None,
+        len if len == destination + 2 => match room_number(&args[destination + 1]) {
+            Some(number) => Some(number),
+            None => return usage(),
+        },
+        _ => return usage(),
+    };
+
+    let room = if from_rms {
+        match room_number(&args[1]) {
+            Some(source) => load_room(&args[0], source)?,
+            None => return usage(),
+        }
+    } else {
+        let text = std::fs::read_to_string(&args[0]).map_err(|e| Error::io(&args[0], e))?;
+        rms_text::room_from_text(&args[0], &text)?
+    };
+    paste_room(&room, &args[destination], number, with_links)
+}
+
+/// Put `room` into an RMS file over room `number`, or at the end if there's no number.
+fn paste_room(
+    room: &rms::Room,
+    rms_filename: &str,
+    number: Option<usize>,
+    with_links: bool,
+) -> error::Result<i32> {
+    let mut dungeon = rms::load_rooms(rms_filename)?;
+    let index = match number {
+        Some(number) if number <= dungeon.rooms.len() => number - 1,
+        Some(number) => {
+            return Err(Error::Format {
+                filename: rms_filename.to_string(),
+                message: format!("there's no room {}", number),
+            })
+        }
+        None => rms::append_room(&mut dungeon.rooms, rms::Room::new("")).ok_or_else(|| {
+            Error::Format {
+                filename: rms_filename.to_string(),
+                message: format!("can't have more than {} rooms", rms::MAX_ROOMS),
+            }
+        })?,
+    };
+    rms::paste_room(&mut dungeon.rooms, index, room, with_links);
+    rms::save_rooms(&dungeon, rms_filename)?;
+    println!("{}: room {}", rms_filename, index + 1);
+    Ok(0)
+}
+
+/// Print every room matching the query, failing if there are none like grep(1).
+fn find(filename: &str, query: &str) -> error::Result<i32> {
+    let query = match query::parse(query) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("rmsedit: query {}", e);
+            return Ok(2);
+        }
+    };
+    let dungeon = rms::load_rooms(filename)?;
+    let matches = query.filter(&dungeon.rooms);
+    for &index in matches.iter() {
+        println!("room {} \"{}\"", index + 1, dungeon.rooms[index].name);
+    }
+    Ok(if matches.is_empty() { 1 } else { 0 })
+}
+
+/// A list of 1-based room numbers and ranges like "1,3-5", as 0-based indices. None for backwards
+/// ranges and rooms past the most a dungeon can have.
+fn room_list(arg: &str) -> Option<Vec<rms::RoomIndex>> {
+    let mut rooms = Vec::new();
+    for part in arg.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (room_number(first)?, room_number(last)?),
+            None => (room_number(part)?, room_number(part)?),
+        };
+        if first > last || last > rms::MAX_ROOMS {
+            return None;
+        }
+        rooms.extend(first - 1..last);
+    }
+    Some(rooms)
+}
+
+/// "X1,Y1,X2,Y2", 0-based and inclusive like the editor's coordinates.
+fn region(arg: &str) -> Option<Region> {
+    let numbers: Vec<u32> = arg
+        .split(',')
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [left, top, right, bottom] if left <= right && top <= bottom => Some(Region {
+            left,
+            top,
+            right,
+            bottom,
+        }),
+        _ => None,
+    }
+}
+
+/// A tile or object byte, either as a number or as the character the game uses for it.
+fn cell_value(arg: &str) -> Option<u8> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_ascii_digit() && c.is_ascii() => Some(c as u8),
+        _ => arg.parse().ok(),
+    }
+}
+
+fn replace(args: &[String]) -> error::Result<i32> {
+    let mut dry_run = false;
+    let mut scope = Scope::everywhere();
+    let mut args = args;
+    while let Some(option) = args.first().filter(|arg| arg.starts_with("--")) {
+        let value = args.get(1).map(String::as_str);
+        match (option.as_str(), value) {
+            ("--dry-run", _) => {
+                dry_run = true;
+                args = &args[1..];
+                continue;
+            }
+            ("--rooms", Some(value)) => match room_list(value) {
+                Some(rooms) => scope.rooms = Some(rooms),
+                None => return usage(),
+            },
+            ("--region", Some(value)) => match region(value) {
+                Some(region) => scope.region = region,
+                None => return usage(),
+            },
+            _ => return usage(),
+        }
+        args = &args[2..];
+    }
+
+    let (filename, kind, from, to) = match args {
+        [filename, kind, from, to] => (filename, kind.as_str(), from, to),
+        _ => return usage(),
+    };
+    let replacement = match (kind, cell_value(from), cell_value(to)) {
+        ("tile", Some(from), Some(to)) => Replacement::Tile {
+            from: rms::Tile::from_byte(from),
+            to: rms::Tile::from_byte(to),
+        },
+        ("object", Some(from), Some(to)) => Replacement::Object {
+            from: RoomObject::from_byte(from),
+            to: RoomObject::from_byte(to),
+        },
+        ("monster", _, _) => match (from.parse(), to.parse()) {
+            (Ok(from), Ok(to)) => Replacement::Monster { from, to },
+            _ => return usage(),
+        },
+        _ => return usage(),
+    };
+
+    let mut dungeon = rms::load_rooms(filename)?;
+    let changes = if dry_run {
+        replace::preview(&dungeon.rooms, replacement, &scope)
+    } else {
+        replace::replace(&mut dungeon.rooms, replacement, &scope)
+    };
+    print!("{}", replace::summarize(&dungeon.rooms, &changes));
+    if !dry_run && !changes.is_empty() {
+        rms::save_rooms(&dungeon, filename)?;
+    }
+    Ok(0)
+}
+
+/// Generate a dungeon, printing the seed so that it can be made again. Without --seed the seed
+/// comes from the clock.
+fn generate(args: &[String]) -> error::Result<i32> {
+    let mut parameters = generate::Parameters {
+        seed: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+        ..generate::Parameters::default()
+    };
+    let mut args = args;
+    while let Some(option) = args.first().filter(|arg| arg.starts_with("--")) {
+        let value = match args.get(1) {
+            Some(value) => value,
+            None => return usage(),
+        };
+        let parsed = match option.as_str() {
+            "--floors" => value.parse().map(|floors| parameters.floors = floors).ok(),
+            "--rooms" => value
+                .parse()
+                .map(|rooms| parameters.rooms_per_floor = rooms)
+                .ok(),
+            "--loops" => value
+                .parse()
+                .map(|loops| parameters.loop_density = loops)
+                .ok(),
+            "--curve" => value
+                .parse()
+                .map(|curve| parameters.monster_curve = curve)
+                .ok(),
+            "--treasure" => value
+                .parse()
+                .map(|treasure| parameters.treasure_density = treasure)
+                .ok(),
+            "--seed" => value.parse().map(|seed| parameters.seed = seed).ok(),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return usage();
+        }
+        args = &args[2..];
+    }
+    let (rms_filename, monsters_filename) = match args {
+        [rms_filename] => (rms_filename.as_str(), "PYMON.DAT"),
+        [rms_filename, monsters_filename] => (rms_filename.as_str(), monsters_filename.as_str()),
+        _ => return usage(),
+    };
+
+    let monsters = monster::load_monsters(monsters_filename)?;
+    let rooms = match generate::generate(&parameters, monsters.len()) {
+        Ok(rooms) => rooms,
+        Err(message) => {
+            eprintln!("rmsedit: {}", message);
+            return Ok(2);
+        }
+    };
+    println!(
+        "{}: {} rooms, seed {}",
+        rms_filename,
+        rooms.len(),
+        parameters.seed
+    );
+    let dungeon = rms::Dungeon {
+        rooms,
+        trailer: Vec::new(),
+    };
+    rms::save_rooms(&dungeon, rms_filename)?;
+    Ok(0)
+}
+
+/// Shuffle a dungeon, printing the seed so that it can be shuffled the same way again and how
+/// each floor's monsters compare to before. Writes over FILE.RMS unless given OUT.RMS.
+fn randomize(args: &[String]) -> error::Result<i32> {
+    let mut rules = randomize::Rules {
+        seed: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+        ..randomize::Rules::default()
+    };
+    let mut args = args;
+    while let Some(option) = args.first().filter(|arg| arg.starts_with("--")) {
+        let value = match args.get(1) {
+            Some(value) => value,
+            None => return usage(),
+        };
+        match option.as_str() {
+            "--seed" => match value.parse() {
+                Ok(seed) => rules.seed = seed,
+                Err(_) => return usage(),
+            },
+            "--tolerance" => match value.parse() {
+                Ok(tolerance) if tolerance >= 0.0 => rules.tolerance = tolerance,
+                _ => return usage(),
+            },
+            "--keep" => {
+                for kept in value.split(',') {
+                    match kept {
+                        "monsters" => rules.monsters = false,
+                        "spawns" => rules.spawns = false,
+                        "chests" => rules.chests = false,
+                        "objects" => rules.objects = false,
+                        _ => return usage(),
+                    }
+                }
+            }
+            _ => return usage(),
+        }
+        args = &args[2..];
+    }
+    let (filename, out_filename) = match args {
+        [filename] => (filename, filename),
+        [filename, out_filename] => (filename, out_filename),
+        _ => return usage(),
+    };
+
+    let mut dungeon = rms::load_rooms(filename)?;
+    let before = randomize::floor_difficulty(&dungeon.rooms);
+    let shuffled = randomize::randomize(&mut dungeon.rooms, &rules);
+    let after = randomize::floor_difficulty(&dungeon.rooms);
+    println!("{}: seed {}", out_filename, rules.seed);
+    println!(
+        "{} rooms got a different monster, moved {} spawn points, {} chests and {} objects",
+        shuffled.monsters, shuffled.spawns, shuffled.chests, shuffled.objects
+    );
+    for (floor, average) in before.iter() {
+        println!(
+            "floor {}: average monster {:.2}, was {:.2}",
+            floor.map_or(String::from("unreachable"), |floor| (floor + 1).to_string()),
+            after[floor],
+            average
+        );
+    }
+    rms::save_rooms(&dungeon, out_filename)?;
+    Ok(0)
+}
+
+/// Draw every room like the editor does, using the game's graphics from the current directory.
+fn contact_sheet(rms_filename: &str, png_filename: &str) -> error::Result<i32> {
+    let dungeon = rms::load_rooms(rms_filename)?;
+    let monsters = monster::load_monsters("PYMON.DAT")?;
+    let tiles = sprites::load_tiles("EGAPICS.PIC")?;
+    let monster_sprites = sprites::load_monsters("PYMON.PIC", "PYMASK.PIC")?;
+
+    let font_error = |message: String| Error::Format {
+        filename: text::FONT_FILENAME.to_string(),
+        message,
+    };
+    let ttf_context = sdl2::ttf::init().map_err(|e| font_error(e.to_string()))?;
+    let font = ttf_context
+        .load_font(text::FONT_FILENAME, text::FONT_SIZE)
+        .map_err(font_error)?;
+
+    let image = sheet::contact_sheet(
+        &dungeon.rooms,
+        &tiles,
+        &monster_sprites,
+        &monsters,
+        CONTACT_SHEET_COLUMNS,
+        &mut |text, color| text::render_image(text, color, &font),
+    );
+    png::save(&image, png_filename)?;
+    Ok(0)
+}
+
+fn patch_create(original_dir: &str, modded_dir: &str, patch_filename: &str) -> error::Result<i32> {
+    let patch = Patch::create(Path::new(original_dir), Path::new(modded_dir))?;
+    std::fs::write(patch_filename, patch.to_json()).map_err(|e| Error::io(patch_filename, e))?;
+    for file in patch.files.iter() {
+        println!("{}: changed", file.filename());
+    }
+    Ok(0)
+}
+
+fn load_patch(patch_filename: &str) -> error::Result<Patch> {
+    let text = std::fs::read_to_string(patch_filename).map_err(|e| Error::io(patch_filename, e))?;
+    Patch::from_json(patch_filename, &text)
+}
+
+/// Print why the game files don't match the patch, failing if there's anything to print.
+fn report_conflicts(conflicts: Vec<Conflict>) -> error::Result<i32> {
+    for conflict in conflicts.iter() {
+        println!("{}", conflict);
+    }
+    Ok(if conflicts.is_empty() { 0 } else { 1 })
+}
@@ -0,0 +1,556 @@
+use std::fmt;
+
+use super::analysis::UNKNOWN_FIELDS;
+use super::error;
+use super::json::Value;
+use super::object::RoomObject;
+use super::rms;
+use super::rms::{Direction, Dungeon, Room, RoomIndex, Tile};
+use super::rms_json;
+use super::rms_json::{hex, Reader};
+
+/// Identifies our JSON documents, in case other tools grow their own.
+const FORMAT: &str = "rmsedit diff";
+const VERSION: i64 = 1;
+
+/// One field of a room that differs. Every change remembers both sides so that it can be checked
+/// against, and undone from, either file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Name {
+        old: String,
+        new: String,
+    },
+    /// Only reported when the leftovers after the name differ, since a rename always changes the
+    /// start of the field. Both sides are the field as saved.
+    NameField {
+        old: [u8; rms::ROOM_RECORD_NAME_SIZE],
+        new: [u8; rms::ROOM_RECORD_NAME_SIZE],
+    },
+    Id {
+        old: u8,
+        new: u8,
+    },
+    MonsterId {
+        old: u8,
+        new: u8,
+    },
+    MonsterCount {
+        old: u8,
+        new: u8,
+    },
+    Link {
+        direction: Direction,
+        old: Option<RoomIndex>,
+        new: Option<RoomIndex>,
+    },
+    /// `name` is one of analysis::UNKNOWN_FIELDS
+    Unknown {
+        name: &'static str,
+        old: u8,
+        new: u8,
+    },
+    Tile {
+        x: u32,
+        y: u32,
+        old: Tile,
+        new: Tile,
+    },
+    Object {
+        x: u32,
+        y: u32,
+        old: RoomObject,
+        new: RoomObject,
+    },
+}
+
+/// How one room differs. Rooms are compared by position, so rooms that only exist in one of the
+/// files are at the end of the longer one.
+#[derive(Clone)]
+pub enum RoomDiff {
+    Changed {
+        room: RoomIndex,
+        /// The room's name in the new file, to make the report easier to follow
+        name: String,
+        changes: Vec<Change>,
+    },
+    Added {
+        room: RoomIndex,
+        contents: Room,
+    },
+    Removed {
+        room: RoomIndex,
+        contents: Room,
+    },
+}
+
+/// Everything that differs between two dungeons.
+#[derive(Clone)]
+pub struct DungeonDiff {
+    /// Sorted by room
+    pub rooms: Vec<RoomDiff>,
+    /// The old and new bytes after the last room, if they differ
+    pub trailer: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+fn cells() -> impl Iterator<Item = (u32, u32)> {
+    (0..rms::ROOM_HEIGHT).flat_map(|y| (0..rms::ROOM_WIDTH).map(move |x| (x, y)))
+}
+
+/// Every field that differs between two versions of a room, in record order.
+pub fn diff_rooms(old: &Room, new: &Room) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if old.name != new.name {
+        changes.push(Change::Name {
+            old: old.name.clone(),
+            new: new.name.clone(),
+        });
+    }
+    let mut renamed = old.clone();
+    renamed.name = new.name.clone();
+    if renamed.saved_name_field() != new.saved_name_field() {
+        changes.push(Change::NameField {
+            old: old.saved_name_field(),
+            new: new.saved_name_field(),
+        });
+    }
+    if old.id() != new.id() {
+        changes.push(Change::Id {
+            old: old.id(),
+            new: new.id(),
+        });
+    }
+    if old.monster_id != new.monster_id {
+        changes.push(Change::MonsterId {
+            old: old.monster_id,
+            new: new.monster_id,
+        });
+    }
+    if old.monster_count != new.monster_count {
+        changes.push(Change::MonsterCount {
+            old: old.monster_count,
+            new: new.monster_count,
+        });
+    }
+    for &direction in Direction::ALL.iter() {
+        if old.link(direction) != new.link(direction) {
+            changes.push(Change::Link {
+                direction,
+                old: old.link(direction),
+                new: new.link(direction),
+            });
+        }
+    }
+    for field in UNKNOWN_FIELDS.iter() {
+        if (field.get)(old) != (field.get)(new) {
+            changes.push(Change::Unknown {
+                name: field.name,
+                old: (field.get)(old),
+                new: (field.get)(new),
+            });
+        }
+    }
+    for (x, y) in cells() {
+        if old.get_tile(x, y) != new.get_tile(x, y) {
+            changes.push(Change::Tile {
+                x,
+                y,
+                old: old.get_tile(x, y),
+                new: new.get_tile(x, y),
+            });
+        }
+    }
+    for (x, y) in cells() {
+        if old.get_object(x, y) != new.get_object(x, y) {
+            changes.push(Change::Object {
+                x,
+                y,
+                old: old.get_object(x, y),
+                new: new.get_object(x, y),
+            });
+        }
+    }
+    changes
+}
+
+/// Compare two dungeons room by room.
+pub fn diff(old: &Dungeon, new: &Dungeon) -> DungeonDiff {
+    let mut rooms = Vec::new();
+    for i in 0..old.rooms.len().max(new.rooms.len()) {
+        match (old.rooms.get(i), new.rooms.get(i)) {
+            (Some(old_room), Some(new_room)) => {
+                let changes = diff_rooms(old_room, new_room);
+                if !changes.is_empty() {
+                    rooms.push(RoomDiff::Changed {
+                        room: i,
+                        name: new_room.name.clone(),
+                        changes,
+                    });
+                }
+            }
+            (None, Some(new_room)) => rooms.push(RoomDiff::Added {
+                room: i,
+                contents: new_room.clone(),
+            }),
+            (Some(old_room), None) => rooms.push(RoomDiff::Removed {
+                room: i,
+                contents: old_room.clone(),
+            }),
+            (None, None) => unreachable!(),
+        }
+    }
+    DungeonDiff {
+        rooms,
+        trailer: if old.trailer != new.trailer {
+            Some((old.trailer.clone(), new.trailer.clone()))
+        } else {
+            None
+        },
+    }
+}
+
+impl DungeonDiff {
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty() && self.trailer.is_none()
+    }
+
+    /// Describe the differences as a JSON document for other tools.
+    pub fn to_json(&self) -> String {
+        let mut members = vec![
+            ("format".to_string(), Value::String(FORMAT.to_string())),
+            ("version".to_string(), Value::Number(VERSION as f64)),
+        ];
+        members.extend(self.to_json_members());
+        Value::Object(members).to_pretty_string()
+    }
+
+    /// The "rooms" and "trailer" members of the JSON document, for documents that embed a diff.
+    pub fn to_json_members(&self) -> Vec<(String, Value)> {
+        let trailer = match &self.trailer {
+            None => Value::Null,
+            Some((old, new)) => Value::Object(vec![
+                ("old".to_string(), hex(old)),
+                ("new".to_string(), hex(new)),
+            ]),
+        };
+        vec![
+            (
+                "rooms".to_string(),
+                Value::Array(self.rooms.iter().map(room_diff_to_json).collect()),
+            ),
+            ("trailer".to_string(), trailer),
+        ]
+    }
+
+    /// Read the members written by to_json_members() from `value`.
+    pub fn from_json_members(reader: &mut Reader, value: &Value) -> error::Result<DungeonDiff> {
+        let room_values = reader
+            .field(value, "rooms")?
+            .as_array()
+            .ok_or_else(|| reader.error(String::from("\"rooms\" must be an array")))?;
+        let mut rooms = Vec::with_capacity(room_values.len());
+        for room_value in room_values.iter() {
+            let room_number = reader.byte_field(room_value, "room")?;
+            let room = (room_number as RoomIndex)
+                .checked_sub(1)
+                .ok_or_else(|| reader.error(String::from("rooms are numbered from 1")))?;
+            let outer_context = std::mem::take(&mut reader.context);
+            reader.context = if outer_context.is_empty() {
+                format!("room {}", room_number)
+            } else {
+                format!("{}: room {}", outer_context, room_number)
+            };
+            rooms.push(match reader.string_field(room_value, "status")? {
+                "changed" => {
+                    let change_values = reader
+                        .field(room_value, "changes")?
+                        .as_array()
+                        .ok_or_else(|| {
+                            reader.error(String::from("\"changes\" must be an array"))
+                        })?;
+                    RoomDiff::Changed {
+                        room,
+                        name: reader.string_field(room_value, "name")?.to_string(),
+                        changes: change_values
+                            .iter()
+                            .map(|change| change_from_json(reader, change))
+                            .collect::<error::Result<Vec<Change>>>()?,
+                    }
+                }
+                "added" => RoomDiff::Added {
+                    room,
+                    contents: reader.room(reader.field(room_value, "contents")?)?,
+                },
+                "removed" => RoomDiff::Removed {
+                    room,
+                    contents: reader.room(reader.field(room_value, "contents")?)?,
+                },
+                _ => {
+                    return Err(reader.error(String::from(
+                        "\"status\" must be \"changed\", \"added\" or \"removed\"",
+                    )))
+                }
+            });
+            reader.context = outer_context;
+        }
+
+        let trailer = match reader.field(value, "trailer")? {
+            Value::Null => None,
+            trailer => Some((
+                reader.hex_field(trailer, "old")?,
+                reader.hex_field(trailer, "new")?,
+            )),
+        };
+        Ok(DungeonDiff { rooms, trailer })
+    }
+}
+
+fn change_from_json(reader: &Reader, value: &Value) -> error::Result<Change> {
+    let byte = |key| reader.byte_field(value, key);
+    let cell = || -> error::Result<(u32, u32)> {
+        let x = byte("x")? as u32;
+        let y = byte("y")? as u32;
+        if x >= rms::ROOM_WIDTH || y >= rms::ROOM_HEIGHT {
+            return Err(reader.error(format!("cell ({}, {}) is outside the room", x, y)));
+        }
+        Ok((x, y))
+    };
+    let name_field = |key| -> error::Result<[u8; rms::ROOM_RECORD_NAME_SIZE]> {
+        let bytes = reader.hex_field(value, key)?;
+        let mut field = [0; rms::ROOM_RECORD_NAME_SIZE];
+        if bytes.len() != field.len() {
+            return Err(reader.error(format!(
+                "\"{}\" must be {} bytes",
+                key,
+                rms::ROOM_RECORD_NAME_SIZE
+            )));
+        }
+        field.copy_from_slice(&bytes);
+        Ok(field)
+    };
+
+    let field = reader.string_field(value, "field")?;
+    if let Some(&direction) = Direction::ALL.iter().find(|d| d.name() == field) {
+        return Ok(Change::Link {
+            direction,
+            old: (byte("old")? as RoomIndex).checked_sub(1),
+            new: (byte("new")? as RoomIndex).checked_sub(1),
+        });
+    }
+    if let Some(unknown) = UNKNOWN_FIELDS.iter().find(|unknown| unknown.name == field) {
+        return Ok(Change::Unknown {
+            name: unknown.name,
+            old: byte("old")?,
+            new: byte("new")?,
+        });
+    }
+    Ok(match field {
+        "name" => Change::Name {
+            old: reader.string_field(value, "old")?.to_string(),
+            new: reader.string_field(value, "new")?.to_string(),
+        },
+        "name_field" => Change::NameField {
+            old: name_field("old")?,
+            new: name_field("new")?,
+        },
+        "id" => Change::Id {
+            old: byte("old")?,
+            new: byte("new")?,
+        },
+        "monster_id" => Change::MonsterId {
+            old: byte("old")?,
+            new: byte("new")?,
+        },
+        "monster_count" => Change::MonsterCount {
+            old: byte("old")?,
+            new: byte("new")?,
+        },
+        "tile" => {
+            let (x, y) = cell()?;
+            Change::Tile {
+                x,
+                y,
+                old: Tile::from_byte(byte("old")?),
+                new: Tile::from_byte(byte("new")?),
+            }
+        }
+        "object" => {
+            let (x, y) = cell()?;
+            Change::Object {
+                x,
+                y,
+                old: RoomObject::from_byte(byte("old")?),
+                new: RoomObject::from_byte(byte("new")?),
+            }
+        }
+        _ => return Err(reader.error(format!("unknown field \"{}\"", field))),
+    })
+}
+
+fn number(n: u32) -> Value {
+    Value::Number(n as f64)
+}
+
+/// 1-based like the game, 0 for no exit
+fn room_number(link: Option<RoomIndex>) -> Value {
+    number(link.map_or(0, |index| index as u32 + 1))
+}
+
+fn change_to_json(change: &Change) -> Value {
+    let member = |key: &str, value: Value| (key.to_string(), value);
+    let field = |name: &str| member("field", Value::String(name.to_string()));
+    let members = match change {
+        Change::Name { old, new } => vec![
+            field("name"),
+            member("old", Value::String(old.clone())),
+            member("new", Value::String(new.clone())),
+        ],
+        Change::NameField { old, new } => vec![
+            field("name_field"),
+            member("old", hex(old)),
+            member("new", hex(new)),
+        ],
+        Change::Id { old, new } => vec![
+            field("id"),
+            member("old", number(*old as u32)),
+            member("new", number(*new as u32)),
+        ],
+        Change::MonsterId { old, new } => vec![
+            field("monster_id"),
+            member("old", number(*old as u32)),
+            member("new", number(*new as u32)),
+        ],
+        Change::MonsterCount { old, new } => vec![
+            field("monster_count"),
+            member("old", number(*old as u32)),
+            member("new", number(*new as u32)),
+        ],
+        Change::Link {
+            direction,
+            old,
+            new,
+        } => vec![
+            field(direction.name()),
+            member("old", room_number(*old)),
+            member("new", room_number(*new)),
+        ],
+        Change::Unknown { name, old, new } => vec![
+            field(name),
+            member("old", number(*old as u32)),
+            member("new", number(*new as u32)),
+        ],
+        Change::Tile { x, y, old, new } => vec![
+            field("tile"),
+            member("x", number(*x)),
+            member("y", number(*y)),
+            member("old", number(old.to_byte() as u32)),
+            member("new", number(new.to_byte() as u32)),
+        ],
+        Change::Object { x, y, old, new } => vec![
+            field("object"),
+            member("x", number(*x)),
+            member("y", number(*y)),
+            member("old", number(old.to_byte() as u32)),
+            member("new", number(new.to_byte() as u32)),
+        ],
+    };
+    Value::Object(members)
+}
+
+fn room_diff_to_json(room_diff: &RoomDiff) -> Value {
+    let member = |key: &str, value: Value| (key.to_string(), value);
+    let status = |status: &str| member("status", Value::String(status.to_string()));
+    Value::Object(match room_diff {
+        RoomDiff::Changed {
+            room,
+            name,
+            changes,
+        } => vec![
+            member("room", number(*room as u32 + 1)),
+            status("changed"),
+            member("name", Value::String(name.clone())),
+            member(
+                "changes",
+                Value::Array(changes.iter().map(change_to_json).collect()),
+            ),
+        ],
+        RoomDiff::Added { room, contents } => vec![
+            member("room", number(*room as u32 + 1)),
+            status("added"),
+            member("contents", rms_json::room_to_json(contents)),
+        ],
+        RoomDiff::Removed { room, contents } => vec![
+            member("room", number(*room as u32 + 1)),
+            status("removed"),
+            member("contents", rms_json::room_to_json(contents)),
+        ],
+    })
+}
+
+fn format_link(link: Option<RoomIndex>) -> String {
+    match link {
+        Some(index) => format!("room {}", index + 1),
+        None => String::from("none"),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Name { old, new } => write!(f, "name: {:?} -> {:?}", old, new),
+            Change::NameField { old, new } => {
+                write!(f, "name field: {:02X?} -> {:02X?}", old, new)
+            }
+            Change::Id { old, new } => write!(f, "id: {} -> {}", old, new),
+            Change::MonsterId { old, new } => write!(f, "monster: {} -> {}", old, new),
+            Change::MonsterCount { old, new } => write!(f, "count: {} -> {}", old, new),
+            Change::Link {
+                direction,
+                old,
+                new,
+            } => write!(
+                f,
+                "{}: {} -> {}",
+                direction.name(),
+                format_link(*old),
+                format_link(*new)
+            ),
+            Change::Unknown { name, old, new } => {
+                write!(f, "{}: {:#04X} -> {:#04X}", name, old, new)
+            }
+            Change::Tile { x, y, old, new } => write!(f, "tile ({}, {}): {} -> {}", x, y, old, new),
+            Change::Object { x, y, old, new } => {
+                write!(f, "object ({}, {}): {} -> {}", x, y, old, new)
+            }
+        }
+    }
+}
+
+impl fmt::Display for DungeonDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for room_diff in self.rooms.iter() {
+            match room_diff {
+                RoomDiff::Changed {
+                    room,
+                    name,
+                    changes,
+                } => {
+                    writeln!(f, "room {} {:?}", room + 1, name)?;
+                    for change in changes.iter() {
+                        writeln!(f, "  {}", change)?;
+                    }
+                }
+                RoomDiff::Added { room, contents } => {
+                    writeln!(f, "room {} {:?}: added", room + 1, contents.name)?
+                }
+                RoomDiff::Removed { room, contents } => {
+                    writeln!(f, "room {} {:?}: removed", room + 1, contents.name)?
+                }
+            }
+        }
+        if let Some((old, new)) = &self.trailer {
+            writeln!(f, "trailer: {:02X?} -> {:02X?}", old, new)?;
+        }
+        Ok(())
+    }
+}
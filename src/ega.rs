@@ -1,5 +1,7 @@
 use super::crumb;
 use super::crumb::crumb;
+use super::error;
+use super::error::Error;
 use super::img;
 
 pub const EGA_HEADER: [u8; 4] = [0x1D, 0x00, 0x0E, 0x00];
@@ -35,15 +37,26 @@ const EGA_PALETTE: [img::Color; 16] = [
     img::Color::rgb(0xFF, 0xFF, 0xFF),
 ];
 
-pub fn load_spritesheet(filename: &str) -> Vec<img::Image> {
-    let pic_data = std::fs::read(filename).unwrap();
-    return pic_data
+pub fn load_spritesheet(filename: &str) -> error::Result<Vec<img::Image>> {
+    let pic_data = std::fs::read(filename).map_err(|e| Error::io(filename, e))?;
+    pic_data
         // Divide the stream of bytes into discrete image sections.
         .chunks(IMAGE_ALIGNMENT)
+        .enumerate()
         // Ignore EGA_HEADER and garbage between images
-        .map(|x| &x[EGA_HEADER.len()..IMAGE_BYTES + EGA_HEADER.len()])
+        .map(|(i, x)| {
+            x.get(EGA_HEADER.len()..IMAGE_BYTES + EGA_HEADER.len())
+                .ok_or_else(|| Error::TruncatedRecord {
+                    filename: filename.to_string(),
+                    record: i,
+                    offset: i * IMAGE_ALIGNMENT,
+                    expected: IMAGE_BYTES + EGA_HEADER.len(),
+                    actual: x.len(),
+                })
+        })
         // Turn byte chunks into images
         .map(|x| {
+            let x = x?;
             // Use an intermediate EGA buffer to accumulate bit channels
             // TODO: Is there a more Rust-idiomatic way of doing this?
             // I want a stride iterator.
@@ -73,14 +86,14 @@ pub fn load_spritesheet(filename: &str) -> Vec<img::Image> {
                     ega_color_buffer[yy * img::IMAGE_DIMENSION_USIZE + xx] |= v << shift_amount;
                 });
 
-            img::Image {
+            Ok(img::Image {
                 width: img::IMAGE_DIMENSION_USIZE,
                 height: img::IMAGE_DIMENSION_USIZE,
                 pixels: ega_color_buffer
                     .iter()
                     .map(|x| EGA_PALETTE[*x as usize])
                     .collect(),
-            }
+            })
         })
-        .collect();
+        .collect()
 }
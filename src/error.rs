@@ -0,0 +1,91 @@
+use std::fmt;
+
+/// Everything that can go wrong while reading or writing the game's data files.
+///
+/// Each variant remembers the file it came from so that a bad file in a large modded data set
+/// can be tracked down without a debugger.
+#[derive(Debug)]
+pub enum Error {
+    /// The file couldn't be read or written at all.
+    Io {
+        filename: String,
+        source: std::io::Error,
+    },
+    /// A fixed-size record ended before all of its fields could be read.
+    TruncatedRecord {
+        filename: String,
+        /// 0-based index of the bad record
+        record: usize,
+        /// Byte offset of the start of the bad record within the file
+        offset: usize,
+        /// How many bytes the record needs
+        expected: usize,
+        /// How many bytes were actually left in the file
+        actual: usize,
+    },
+    /// A .PIC file started with something other than the CGA or EGA header.
+    UnknownPicHeader { filename: String, header: Vec<u8> },
+    /// A text file (JSON, etc.) couldn't be parsed or doesn't describe something that fits in the
+    /// game's files.
+    Format { filename: String, message: String },
+    /// A length-prefixed string couldn't be decoded.
+    InvalidString {
+        filename: String,
+        record: usize,
+        /// Byte offset of the length prefix within the file
+        offset: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Convenience function for wrapping std::io errors, which don't know their filename.
+    pub fn io(filename: &str, source: std::io::Error) -> Error {
+        Error::Io {
+            filename: filename.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { filename, source } => write!(f, "{}: {}", filename, source),
+            Error::TruncatedRecord {
+                filename,
+                record,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: record {} at offset {:#X} is truncated (expected {} bytes, found {})",
+                filename, record, offset, expected, actual
+            ),
+            Error::UnknownPicHeader { filename, header } => {
+                write!(f, "{}: unknown PIC header {:02X?}", filename, header)
+            }
+            Error::Format { filename, message } => write!(f, "{}: {}", filename, message),
+            Error::InvalidString {
+                filename,
+                record,
+                offset,
+            } => write!(
+                f,
+                "{}: record {} has an invalid string at offset {:#X}",
+                filename, record, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
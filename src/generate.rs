@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use super::graph;
+use super::layout::Position;
+use super::object::RoomObject;
+use super::random::Random;
+use super::rms;
+use super::rms::{Direction, Room, RoomIndex, Tile, ROOM_HEIGHT, ROOM_WIDTH};
+use super::walk;
+
+const WALL_TILE: u8 = 1;
+const FLOOR_TILE: u8 = rms::TRAP_BASE_TILE;
+
+/// Every room's openings are in the middle of its edges, so any two neighbours line up.
+const OPENING_COLUMNS: [u32; 2] = [9, 10];
+const OPENING_ROWS: [u32; 2] = [3, 4];
+/// Most pillars tried in a room. Ones that would wall something off are taken back out.
+const MAX_PILLARS: usize = 8;
+const MAX_SPAWNS: usize = 3;
+/// More chests than this per room wouldn't leave space to walk.
+const MAX_TREASURE_DENSITY: f64 = 20.0;
+
+/// What kind of dungeon to make.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameters {
+    pub floors: usize,
+    pub rooms_per_floor: usize,
+    /// From 0 to 1, the chance that two rooms next to each other get linked when they don't need
+    /// to be. 0 makes every floor a tree, with only one way between any two rooms.
+    pub loop_density: f64,
+    /// How monsters get tougher from the top floor to the bottom one, going through PYMON.DAT in
+    /// order: 1 is steady, above 1 stays easy for longer and below 1 gets hard sooner. We don't
+    /// know which fields make a monster tough, so this relies on the list getting tougher towards
+    /// the end.
+    pub monster_curve: f64,
+    /// Average treasure chests per room
+    pub treasure_density: f64,
+    pub seed: u64,
+}
+
+impl Default for Parameters {
+    fn default() -> Parameters {
+        Parameters {
+            floors: 3,
+            rooms_per_floor: 12,
+            loop_density: 0.2,
+            monster_curve: 1.0,
+            treasure_density: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+impl Parameters {
+    /// Why these parameters can't make a dungeon, if they can't.
+    pub fn check(&self) -> Result<(), String> {
+        if self.floors == 0 || self.rooms_per_floor == 0 {
+            Err(String::from(
+                "there must be at least one floor and one room per floor",
+            ))
+        } else if self.floors * self.rooms_per_floor > rms::MAX_ROOMS {
+            Err(format!(
+                "{} floors of {} rooms is more than {} rooms",
+                self.floors,
+                self.rooms_per_floor,
+                rms::MAX_ROOMS
+            ))
+        } else if !(0.0..=1.0).contains(&self.loop_density) {
+            Err(String::from("loop density must be from 0 to 1"))
+        } else if self.monster_curve.is_nan() || self.monster_curve <= 0.0 {
+            Err(String::from("monster curve must be more than 0"))
+        } else if !(0.0..=MAX_TREASURE_DENSITY).contains(&self.treasure_density) {
+            Err(format!(
+                "treasure density must be from 0 to {}",
+                MAX_TREASURE_DENSITY
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Make a new dungeon for a game with `monster_count` monsters in PYMON.DAT. Each floor is grown
+/// on a grid from the room the ladder down from the floor above arrives in, and ends with the
+/// room furthest from there having the ladder down to the next floor. Room 1 is the entrance.
+pub fn generate(parameters: &Parameters, monster_count: usize) -> Result<Vec<Room>, String> {
+    parameters.check()?;
+    let mut random = Random::new(parameters.seed);
+    let mut rooms = Vec::new();
+    let mut positions: Vec<Position> = Vec::new();
+    let mut occupied: HashMap<Position, RoomIndex> = HashMap::new();
+    let mut ladder_down: Option<RoomIndex> = None;
+
+    for floor in 0..parameters.floors {
+        let first = rooms.len();
+        for number in 0..parameters.rooms_per_floor {
+            let name = format!("Floor {}, room {}", floor + 1, number + 1);
+            let index = rms::append_room(&mut rooms, Room::new(&name)).unwrap();
+            let position = if number == 0 {
+                match ladder_down {
+                    Some(above) => {
+                        rms::link_rooms(&mut rooms, above, Direction::Down, index);
+                        positions[above].step(Direction::Down)
+                    }
+                    None => Position {
+                        x: 0,
+                        y: 0,
+                        floor: 0,
+                    },
+                }
+            } else {
+                // Grow off a random room already on this floor, which links the floor as a tree
+                loop {
+                    let from = first + random.below(index - first);
+                    let direction = Direction::ALL[random.below(4)];
+                    let position = positions[from].step(direction);
+                    if !occupied.contains_key(&position) {
+                        rms::link_rooms(&mut rooms, from, direction, index);
+                        break position;
+                    }
+                }
+            };
+            positions.push(position);
+            occupied.insert(position, index);
+        }
+
+        // Loops between neighbours that aren't linked yet
+        for index in first..rooms.len() {
+            for &direction in [Direction::East, Direction::South].iter() {
+                let neighbour = match occupied.get(&positions[index].step(direction)) {
+                    Some(&neighbour) => neighbour,
+                    None => continue,
+                };
+                if rooms[index].link(direction).is_none() && random.chance(parameters.loop_density)
+                {
+                    rms::link_rooms(&mut rooms, index, direction, neighbour);
+                }
+            }
+        }
+
+        let distances = graph::distances_from(&rooms, first);
+        ladder_down = (first..rooms.len()).max_by_key(|&index| distances[index]);
+    }
+
+    for (index, room) in rooms.iter_mut().enumerate() {
+        build_room(room, &mut random);
+        let floor = positions[index].floor as usize;
+        furnish_room(room, index, floor, parameters, monster_count, &mut random);
+    }
+    Ok(rooms)
+}
+
+/// Whether the player can get everywhere in the room, from every exit.
+fn is_open(room: &Room) -> bool {
+    let walkability = walk::walkability(room);
+    let exits: Vec<Direction> = Direction::ALL[..4]
+        .iter()
+        .copied()
+        .filter(|&direction| room.link(direction).is_some())
+        .collect();
+    let everywhere = (0..ROOM_HEIGHT).all(|y| {
+        (0..ROOM_WIDTH).all(|x| {
+            !walk::cell_passability(room, x, y).is_passable() || walkability.is_reachable(x, y)
+        })
+    });
+    everywhere
+        && exits
+            .iter()
+            .all(|&direction| walkability.connects(exits[0], direction))
+}
+
+/// Walls around the edge with openings for the room's exits, and a few pillars inside.
+fn build_room(room: &mut Room, random: &mut Random) {
+    for y in 0..ROOM_HEIGHT {
+        for x in 0..ROOM_WIDTH {
+            let is_edge = x == 0 || y == 0 || x == ROOM_WIDTH - 1 || y == ROOM_HEIGHT - 1;
+            let tile = if is_edge { WALL_TILE } else { FLOOR_TILE };
+            room.set_tile(x, y, Tile::Graphic(tile));
+            room.set_object(x, y, RoomObject::None);
+        }
+    }
+    for &direction in Direction::ALL[..4].iter() {
+        if room.link(direction).is_none() {
+            continue;
+        }
+        for (x, y) in walk::edge_cells(direction) {
+            let is_opening = match direction {
+                Direction::North | Direction::South => OPENING_COLUMNS.contains(&x),
+                _ => OPENING_ROWS.contains(&y),
+            };
+            if is_opening {
+                room.set_tile(x, y, Tile::Graphic(FLOOR_TILE));
+            }
+        }
+    }
+
+    for _ in 0..random.below(MAX_PILLARS + 1) {
+        let x = 1 + random.below(ROOM_WIDTH as usize - 2) as u32;
+        let y = 1 + random.below(ROOM_HEIGHT as usize - 2) as u32;
+        room.set_tile(x, y, Tile::Graphic(WALL_TILE));
+        if !is_open(room) {
+            room.set_tile(x, y, Tile::Graphic(FLOOR_TILE));
+        }
+    }
+}
+
+/// Treasure chests and monsters, on floor that isn't next to an opening so nothing blocks the
+/// way in. The entrance doesn't get monsters.
+fn furnish_room(
+    room: &mut Room,
+    index: RoomIndex,
+    floor: usize,
+    parameters: &Parameters,
+    monster_count: usize,
+    random: &mut Random,
+) {
+    let mut free: Vec<(u32, u32)> = Vec::new();
+    for y in 2..ROOM_HEIGHT - 2 {
+        for x in 2..ROOM_WIDTH - 2 {
+            if room.get_tile(x, y) == Tile::Graphic(FLOOR_TILE) {
+                free.push((x, y));
+            }
+        }
+    }
+    let mut place = |room: &mut Room, object: RoomObject, random: &mut Random| {
+        if !free.is_empty() {
+            let (x, y) = free.swap_remove(random.below(free.len()));
+            room.set_object(x, y, object);
+        }
+    };
+
+    let density = parameters.treasure_density;
+    let chests = density as usize + random.chance(density.fract()) as usize;
+    for _ in 0..chests {
+        place(room, RoomObject::TreasureChest, random);
+    }
+
+    if index == 0 || monster_count == 0 {
+        return;
+    }
+    let depth = if parameters.floors > 1 {
+        floor as f64 / (parameters.floors - 1) as f64
+    } else {
+        0.0
+    };
+    // Monster ids are a byte
+    let toughest = monster_count.min(u8::MAX as usize) as i64 - 1;
+    let target = (depth.powf(parameters.monster_curve) * toughest as f64).round() as i64;
+    // A little variety either side of the curve
+    let monster = (target + random.below(3) as i64 - 1).clamp(0, toughest);
+    room.monster_id = monster as u8 + 1;
+    for _ in 0..1 + random.below(MAX_SPAWNS) {
+        place(room, RoomObject::MONSTER_SPAWN, random);
+    }
+    room.monster_count = room.monster_spawns().len() as u8;
+}
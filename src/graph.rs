@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+
+use super::rms::{Direction, Room, RoomIndex};
+
+/// How many moves it takes to get from `start` to each room, or None if the room can't be
+/// reached. Links past the end of the room list are ignored.
+pub fn distances_from(rooms: &[Room], start: RoomIndex) -> Vec<Option<usize>> {
+    let mut distances = vec![None; rooms.len()];
+    if start >= rooms.len() {
+        return distances;
+    }
+    let mut queue = VecDeque::new();
+    distances[start] = Some(0);
+    queue.push_back(start);
+    while let Some(index) = queue.pop_front() {
+        let distance = distances[index].unwrap_or_default();
+        for &direction in Direction::ALL.iter() {
+            if let Some(target) = rooms[index].link(direction) {
+                if target < rooms.len() && distances[target].is_none() {
+                    distances[target] = Some(distance + 1);
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+    distances
+}
+
+/// Which floor each room is on, counting down from `start` on floor 0: going down a ladder adds
+/// one, going up subtracts one. None if the room can't be reached. If the links disagree about a
+/// room's floor then the first path found wins.
+pub fn floors_from(rooms: &[Room], start: RoomIndex) -> Vec<Option<i32>> {
+    let mut floors = vec![None; rooms.len()];
+    if start >= rooms.len() {
+        return floors;
+    }
+    let mut queue = VecDeque::new();
+    floors[start] = Some(0);
+    queue.push_back(start);
+    while let Some(index) = queue.pop_front() {
+        let floor = floors[index].unwrap_or_default();
+        for &direction in Direction::ALL.iter() {
+            if let Some(target) = rooms[index].link(direction) {
+                if target < rooms.len() && floors[target].is_none() {
+                    floors[target] = Some(match direction {
+                        Direction::Up => floor - 1,
+                        Direction::Down => floor + 1,
+                        _ => floor,
+                    });
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+    floors
+}
+
+/// The shortest way from `from` to `to` as the moves to make and the room each one leads to, or
+/// None if there isn't one. Empty if they're the same room.
+pub fn route(
+    rooms: &[Room],
+    from: RoomIndex,
+    to: RoomIndex,
+) -> Option<Vec<(Direction, RoomIndex)>> {
+    if from >= rooms.len() || to >= rooms.len() {
+        return None;
+    }
+    // How each room was first reached
+    let mut previous: Vec<Option<(RoomIndex, Direction)>> = vec![None; rooms.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(index) = queue.pop_front() {
+        if index == to {
+            break;
+        }
+        for &direction in Direction::ALL.iter() {
+            if let Some(target) = rooms[index].link(direction) {
+                if target < rooms.len() && target != from && previous[target].is_none() {
+                    previous[target] = Some((index, direction));
+                    queue.push_back(target);
+                }
+            }
+        }
+    }
+
+    let mut moves = Vec::new();
+    let mut index = to;
+    while index != from {
+        let (source, direction) = previous[index]?;
+        moves.push((direction, index));
+        index = source;
+    }
+    moves.reverse();
+    Some(moves)
+}
+
+/// Moves as they'd be written on paper, like "E, E, Down, S".
+pub fn describe_route(moves: &[(Direction, RoomIndex)]) -> String {
+    let names: Vec<&str> = moves
+        .iter()
+        .map(|(direction, _)| match direction {
+            Direction::North => "N",
+            Direction::East => "E",
+            Direction::South => "S",
+            Direction::West => "W",
+            Direction::Up => "Up",
+            Direction::Down => "Down",
+        })
+        .collect();
+    names.join(", ")
+}
@@ -1,11 +1,13 @@
 use super::cga;
 use super::ega;
+use super::error;
+use super::error::Error;
 
 /// Width and height
 pub const IMAGE_DIMENSION: u32 = 15;
 pub const IMAGE_DIMENSION_USIZE: usize = IMAGE_DIMENSION as usize;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -31,16 +33,15 @@ pub struct Image {
     pub pixels: Vec<Color>,
 }
 
-pub fn load_spritesheet(filename: &str) -> Vec<Image> {
-    fn header_match(to_match: &[u8]) -> [u8; 4] {
-        [to_match[0], to_match[1], to_match[2], to_match[3]]
-    }
-
+pub fn load_spritesheet(filename: &str) -> error::Result<Vec<Image>> {
     // TODO: optimize (e.g. don't load pic_data twice)
-    let pic_data = std::fs::read(filename).unwrap();
-    match header_match(&pic_data[0..4]) {
-        cga::CGA_HEADER => return cga::load_spritesheet(filename),
-        ega::EGA_HEADER => return ega::load_spritesheet(filename),
-        _ => panic!("no matching header"), // TODO return result with error instead
+    let pic_data = std::fs::read(filename).map_err(|e| Error::io(filename, e))?;
+    match pic_data.get(0..4) {
+        Some(header) if header == cga::CGA_HEADER => cga::load_spritesheet(filename),
+        Some(header) if header == ega::EGA_HEADER => ega::load_spritesheet(filename),
+        _ => Err(Error::UnknownPicHeader {
+            filename: filename.to_string(),
+            header: pic_data.iter().take(4).cloned().collect(),
+        }),
     }
 }
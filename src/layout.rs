@@ -0,0 +1,143 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::rms::{Direction, Room, RoomIndex};
+
+/// Where a room is drawn on the map. `x` grows to the east, `y` to the south and `floor` going
+/// down ladders.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub floor: i32,
+}
+
+impl Position {
+    /// The position one step in `direction`.
+    pub fn step(self, direction: Direction) -> Position {
+        let Position { x, y, floor } = self;
+        match direction {
+            Direction::North => Position { x, y: y - 1, floor },
+            Direction::East => Position { x: x + 1, y, floor },
+            Direction::South => Position { x, y: y + 1, floor },
+            Direction::West => Position { x: x - 1, y, floor },
+            Direction::Up => Position {
+                x,
+                y,
+                floor: floor - 1,
+            },
+            Direction::Down => Position {
+                x,
+                y,
+                floor: floor + 1,
+            },
+        }
+    }
+}
+
+/// Every room placed on a grid.
+pub struct Layout {
+    /// Indexed by room
+    pub positions: Vec<Position>,
+    /// Links that don't lead to the neighbouring cell, because the dungeon isn't a consistent
+    /// grid there (loops that come back somewhere else, two rooms in the same spot, ...)
+    pub contradictions: Vec<(RoomIndex, Direction)>,
+}
+
+impl Layout {
+    /// Whether any of the room's links, or links into it, contradict the grid.
+    pub fn is_contradicted(&self, rooms: &[Room], room: RoomIndex) -> bool {
+        self.contradictions
+            .iter()
+            .any(|&(from, direction)| from == room || rooms[from].link(direction) == Some(room))
+    }
+
+    /// All the floors with rooms on them, from the top down.
+    pub fn floors(&self) -> Vec<i32> {
+        let mut floors: Vec<i32> = self.positions.iter().map(|p| p.floor).collect();
+        floors.sort_unstable();
+        floors.dedup();
+        floors
+    }
+}
+
+/// The free cell closest to `wanted` on its floor, searching outwards in rings.
+fn nearest_free(occupied: &HashMap<Position, RoomIndex>, wanted: Position) -> Position {
+    for radius in 0i32.. {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                if dx.abs() != radius && dy.abs() != radius {
+                    continue;
+                }
+                let candidate = Position {
+                    x: wanted.x + dx,
+                    y: wanted.y + dy,
+                    floor: wanted.floor,
+                };
+                if !occupied.contains_key(&candidate) {
+                    return candidate;
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Place rooms on a grid by following their links outwards from `start`, one floor per ladder.
+/// Rooms that can't be reached from `start` are laid out the same way, off to the east. When two
+/// rooms want the same cell the later one is moved to the nearest free cell and the link is
+/// reported as a contradiction.
+pub fn layout(rooms: &[Room], start: RoomIndex) -> Layout {
+    let mut positions: Vec<Option<Position>> = vec![None; rooms.len()];
+    let mut occupied: HashMap<Position, RoomIndex> = HashMap::new();
+    let mut contradictions = Vec::new();
+
+    let seeds = std::iter::once(start).chain(0..rooms.len());
+    for seed in seeds.filter(|&seed| seed < rooms.len()) {
+        if positions[seed].is_some() {
+            continue;
+        }
+        // Leave a gap to the east of everything placed so far
+        let x = occupied.keys().map(|p| p.x + 2).max().unwrap_or(0);
+        let seed_position = nearest_free(&occupied, Position { x, y: 0, floor: 0 });
+        positions[seed] = Some(seed_position);
+        occupied.insert(seed_position, seed);
+
+        let mut queue = VecDeque::new();
+        queue.push_back(seed);
+        while let Some(index) = queue.pop_front() {
+            let position = positions[index].unwrap();
+            for &direction in Direction::ALL.iter() {
+                let target = match rooms[index].link(direction) {
+                    Some(target) if target < rooms.len() => target,
+                    _ => continue,
+                };
+                let wanted = position.step(direction);
+                match positions[target] {
+                    Some(placed) => {
+                        // Two-way links are only reported from the side found first
+                        let is_reported = contradictions.contains(&(target, direction.opposite()))
+                            && rooms[target].link(direction.opposite()) == Some(index);
+                        if placed != wanted && !is_reported {
+                            contradictions.push((index, direction));
+                        }
+                    }
+                    None => {
+                        let placed = nearest_free(&occupied, wanted);
+                        if placed != wanted {
+                            contradictions.push((index, direction));
+                        }
+                        positions[target] = Some(placed);
+                        occupied.insert(placed, target);
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+    }
+
+    contradictions.sort_by_key(|&(room, direction)| (room, direction as usize));
+    Layout {
+        positions: positions.into_iter().map(Option::unwrap).collect(),
+        contradictions,
+    }
+}
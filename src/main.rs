@@ -1,20 +1,50 @@
+use std::collections::HashMap;
+
 use sdl2::event::Event;
+use sdl2::keyboard::Mod;
 use sdl2::keyboard::Scancode;
 use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::render::Canvas;
 use sdl2::render::Texture;
 use sdl2::render::TextureCreator;
 use sdl2::surface::Surface;
+use sdl2::ttf::Font;
+use sdl2::video::Window;
 
+mod analysis;
 mod cga;
+mod cli;
+mod cp437;
 mod crumb;
+mod diff;
 mod ega;
+mod error;
+mod generate;
+mod graph;
 mod img;
+mod json;
+mod layout;
 mod monster;
+mod object;
 mod pascal;
+mod patch;
+mod png;
+mod query;
+mod random;
+mod randomize;
+mod replace;
 mod rms;
+mod rms_json;
+mod rms_text;
+mod sheet;
+mod sprites;
+mod stats;
 mod text;
+mod validate;
+mod walk;
 
 mod tests;
 
@@ -46,16 +76,104 @@ impl EditorView {
     }
 }
 
-fn apply_mask(image: &mut img::Image, mask_image: &img::Image) {
-    image
-        .pixels
-        .iter_mut()
-        .zip(mask_image.pixels.iter())
-        .for_each(|(pixel, mask)| {
-            if *mask == img::Color::rgb(0xFF, 0xFF, 0xFF) {
-                pixel.a = 0;
-            }
-        });
+/// Where the map draws each room's miniature. The current room is always in the middle.
+struct MapView {
+    /// Center of the map on screen, in pixels
+    pub center_x: i32,
+    pub center_y: i32,
+    /// Width and height of miniature tiles, in pixels
+    pub tile_dimensions: u32,
+    /// Gap between neighbouring miniatures, in pixels
+    pub spacing: u32,
+}
+
+impl MapView {
+    /// Where the room at `position` goes when the room at `center` is in the middle.
+    fn room_rect(&self, position: layout::Position, center: layout::Position) -> Rect {
+        let width = rms::ROOM_WIDTH * self.tile_dimensions;
+        let height = rms::ROOM_HEIGHT * self.tile_dimensions;
+        Rect::new(
+            self.center_x + (position.x - center.x) * (width + self.spacing) as i32
+                - width as i32 / 2,
+            self.center_y + (position.y - center.y) * (height + self.spacing) as i32
+                - height as i32 / 2,
+            width,
+            height,
+        )
+    }
+    fn tile_rect(&self, room_rect: Rect, x: u32, y: u32) -> Rect {
+        Rect::new(
+            room_rect.x() + (x * self.tile_dimensions) as i32,
+            room_rect.y() + (y * self.tile_dimensions) as i32,
+            self.tile_dimensions,
+            self.tile_dimensions,
+        )
+    }
+}
+
+/// What left clicking paints, and into which layer.
+#[derive(Copy, Clone)]
+enum Brush {
+    Tile(rms::Tile),
+    Object(object::RoomObject),
+}
+
+impl Brush {
+    fn paint(self, room: &mut rms::Room, x: u32, y: u32) {
+        match self {
+            Brush::Tile(tile) => room.set_tile(x, y, tile),
+            Brush::Object(object) => room.set_object(x, y, object),
+        }
+    }
+
+    /// Pick up whatever is at (x, y), preferring objects since they're drawn on top.
+    fn pick(room: &rms::Room, x: u32, y: u32) -> Brush {
+        match room.get_object(x, y) {
+            object::RoomObject::None => Brush::Tile(room.get_tile(x, y)),
+            object => Brush::Object(object),
+        }
+    }
+}
+
+impl std::fmt::Display for Brush {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Brush::Tile(tile) => write!(f, "{}", tile),
+            Brush::Object(object) => write!(f, "{}", object),
+        }
+    }
+}
+
+/// One line description of the room's monster settings.
+fn summarize_monsters(room: &rms::Room) -> String {
+    format!(
+        "Monster {} x {}, {} spawn points",
+        room.monster_id,
+        room.monster_count,
+        room.monster_spawns().len()
+    )
+}
+
+/// Draw `character` centered on `rect`, rendering it the first time it's needed. Used to mark
+/// things we don't have a sprite for.
+fn draw_marker<'r, T>(
+    canvas: &mut Canvas<Window>,
+    markers: &mut HashMap<(char, Color), text::TextRendering<'r>>,
+    character: char,
+    color: Color,
+    font: &Font,
+    texture_creator: &'r TextureCreator<T>,
+    rect: Rect,
+) {
+    let marker = markers.entry((character, color)).or_insert_with(|| {
+        text::TextRendering::from_text(&character.to_string(), &color, font, texture_creator)
+    });
+    let marker_rect = marker.rect(0, 0);
+    let marker_rect = marker.rect(
+        rect.center().x() - marker_rect.width() as i32 / 2,
+        rect.center().y() - marker_rect.height() as i32 / 2,
+    );
+    canvas.copy(marker.texture(), None, marker_rect).unwrap();
 }
 
 // TODO: Return Result<> since multiple operations can fail?
@@ -87,28 +205,74 @@ fn as_texture<'a, T>(image: &img::Image, texture_creator: &'a TextureCreator<T>)
     return surface.as_texture(texture_creator).unwrap();
 }
 
+/// Report a failed file operation to the user and quit instead of panicking.
+fn exit_on_error<T>(result: error::Result<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("rmsedit: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The window title shows the query being typed after /, or the filter in use.
+fn window_title(
+    query_input: &Option<String>,
+    room_filter: &Option<(String, query::Query)>,
+) -> String {
+    match (query_input, room_filter) {
+        (Some(input), _) => format!("rmsedit - find: {}_", input),
+        (None, Some((text, _))) => format!("rmsedit - filter: {}", text),
+        (None, None) => String::from("rmsedit"),
+    }
+}
+
 /// Convenience function for pushing a paint event
 fn request_paint(event_subsystem: &sdl2::EventSubsystem) {
     event_subsystem.push_custom_event(PaintEvent {}).unwrap();
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(exit_on_error(cli::run(&args)));
+    }
+
     let editor_view = EditorView {
         x: 8,
         y: 16,
         tile_dimensions: img::IMAGE_DIMENSION * 2,
     };
-    let mut rooms = rms::load_rooms("DUNGEON.RMS");
-    let monsters = monster::load_monsters("PYMON.DAT");
+    // Every monster in PYMON.DAT, to pick the current room's monster from
+    let monster_view = EditorView {
+        x: 8,
+        y: 372,
+        tile_dimensions: img::IMAGE_DIMENSION * 2,
+    };
+    const MONSTER_VIEW_COLUMNS: u32 = 20;
+    let map_view = MapView {
+        center_x: 320,
+        center_y: 220,
+        tile_dimensions: 3,
+        spacing: 20,
+    };
+    let mut dungeon = exit_on_error(rms::load_rooms("DUNGEON.RMS"));
+    let monsters = exit_on_error(monster::load_monsters("PYMON.DAT"));
+    let validate_dungeon = |rooms: &[rms::Room]| {
+        let mut issues = validate::validate(rooms);
+        issues.extend(validate::validate_monsters(rooms, &monsters));
+        issues.extend(validate::validate_walkability(rooms));
+        issues.extend(validate::validate_edges(rooms));
+        issues.sort_by_key(|issue| issue.room);
+        issues
+    };
 
     let sdl_context = sdl2::init().unwrap();
 
     let ttf_context = sdl2::ttf::init().unwrap();
     let liberation_sans = ttf_context
-        .load_font(
-            "fonts/liberation-fonts-ttf-2.1.2/LiberationSans-Regular.ttf",
-            16,
-        )
+        .load_font(text::FONT_FILENAME, text::FONT_SIZE)
         .unwrap();
 
     let event_subsystem = sdl_context.event().unwrap();
@@ -118,44 +282,15 @@ fn main() {
 
     let video_subsystem = sdl_context.video().unwrap();
     let window = video_subsystem
-        .window("Hello", 640, 480)
+        .window(&window_title(&None, &None), 640, 480)
         .position_centered()
         .build()
         .unwrap();
     let mut canvas = window.into_canvas().build().unwrap();
     let texture_creator = canvas.texture_creator();
 
-    let mut monster_color = img::load_spritesheet("PYMON.PIC");
-    let monster_mask = img::load_spritesheet("PYMASK.PIC");
-    monster_color
-        .iter_mut()
-        .zip(monster_mask.iter())
-        .for_each(|(color, mask)| apply_mask(color, mask));
-
-    // EGAPICS/CGAPICS contains both color data and masks. In order to use apply_mask
-    // (inside Rust's borrow rules) I load a second immutable copy. Alternatively, I could
-    // just implement Copy on Image... Or I could return a copy from apply_mask and not take
-    // a mutable reference
-    let mut tiles_color = img::load_spritesheet("EGAPICS.PIC");
-    let tiles_mask = img::load_spritesheet("EGAPICS.PIC");
-    // Only a handful of EGAPICS/CGAPICS tiles have masks.
-    vec![
-        (10, 64), // Attack effect
-        (11, 69), // Hit explosion
-        (18, 71), // Old bones
-        (22, 65), // Treasure chest
-        (23, 70), // Old body
-        (24, 68), // Player
-        (47, 67), // Smoke
-        (50, 72), // Old stone coffin
-        (55, 66), // Old grave
-        (60, 82), // ???
-        (75, 73), // ???
-        (76, 74), // ???
-        (83, 84), // Some old blood
-    ]
-    .iter()
-    .for_each(|(tile, mask)| apply_mask(&mut tiles_color[*tile - 1], &tiles_mask[*mask - 1]));
+    let monster_color = exit_on_error(sprites::load_monsters("PYMON.PIC", "PYMASK.PIC"));
+    let tiles_color = exit_on_error(sprites::load_tiles("EGAPICS.PIC"));
 
     let tiles_atlas: Vec<sdl2::render::Texture> = tiles_color
         .iter()
@@ -166,11 +301,60 @@ fn main() {
         .map(|x| as_texture(x, &texture_creator))
         .collect();
 
-    let mut room_index: usize = 0;
+    let mut room_index: rms::RoomIndex = 0;
     let mut is_dragging = false;
+    // What left clicking paints into the tile layer
+    let mut brush = Brush::Tile(rms::Tile::Graphic(1));
+    // Rendered on demand since most dungeons only use a few kinds of trap
+    let mut markers = HashMap::new();
+    // Room picked with M as the other end for Shift+direction linking
+    let mut marked_room: Option<rms::RoomIndex> = None;
+    // Tab switches between editing the current room and the map of its floor
+    let mut show_map = false;
+    // I shows the dungeon's statistics instead
+    let mut show_stats = false;
+    // W shades where the player can walk in the current room
+    let mut show_walkability = false;
+    // Where the mouse was last seen, for keys that act on the cell under it
+    let mut mouse_position = (0, 0);
+    // The query being typed after /. Keys type instead of editing until Return or Escape.
+    let mut query_input: Option<String> = None;
+    // Rooms matching the last query are outlined on the map and stepped through with G
+    let mut room_filter: Option<(String, query::Query)> = None;
+    // Text input is only wanted while typing a query
+    video_subsystem.text_input().stop();
+    // Rooms picked with K, in order. The map shows the shortest route through them.
+    let mut key_rooms: Vec<rms::RoomIndex> = Vec::new();
+    // A replacement that's been previewed with R, made if R is pressed again the same way
+    let mut pending_replace: Option<(replace::Replacement, replace::Scope)> = None;
 
     let mut room_text = text::TextLabel::new(
-        &rooms[room_index].name,
+        &dungeon.rooms[room_index].name,
+        Color::RGB(0xFF, 0xFF, 0xFF),
+        &liberation_sans,
+        &texture_creator,
+    );
+
+    let mut issues = validate_dungeon(&dungeon.rooms);
+    let mut issues_summary = validate::summarize(&issues, room_index);
+    let mut issues_text = text::TextLabel::new(
+        &issues_summary,
+        Color::RGB(0xFF, 0xFF, 0x55),
+        &liberation_sans,
+        &texture_creator,
+    );
+
+    let mut monster_summary = summarize_monsters(&dungeon.rooms[room_index]);
+    let mut monster_text = text::TextLabel::new(
+        &monster_summary,
+        Color::RGB(0xFF, 0xFF, 0xFF),
+        &liberation_sans,
+        &texture_creator,
+    );
+
+    let mut map_summary = String::from("Map");
+    let mut map_text = text::TextLabel::new(
+        &map_summary,
         Color::RGB(0xFF, 0xFF, 0xFF),
         &liberation_sans,
         &texture_creator,
@@ -181,66 +365,443 @@ fn main() {
         for event in event_pump.wait_iter() {
             match event {
                 Event::Quit { .. } => break 'mainloop,
+                Event::TextInput { text, .. } => {
+                    if let Some(input) = query_input.as_mut() {
+                        input.push_str(&text);
+                        let title = window_title(&query_input, &room_filter);
+                        canvas.window_mut().set_title(&title).unwrap();
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    ..
+                } if query_input.is_some() => {
+                    match scancode {
+                        Scancode::Backspace => {
+                            query_input.as_mut().unwrap().pop();
+                        }
+                        Scancode::Escape => query_input = None,
+                        Scancode::Return => {
+                            // An empty query clears the filter
+                            let input = query_input.take().unwrap();
+                            if input.trim().is_empty() {
+                                room_filter = None;
+                            } else {
+                                match query::parse(&input) {
+                                    Ok(query) => {
+                                        let matches = query.filter(&dungeon.rooms);
+                                        println!("{} rooms match {}", matches.len(), input);
+                                        room_filter = Some((input, query));
+                                    }
+                                    Err(e) => eprintln!("rmsedit: query {}", e),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    if query_input.is_none() {
+                        video_subsystem.text_input().stop();
+                    }
+                    let title = window_title(&query_input, &room_filter);
+                    canvas.window_mut().set_title(&title).unwrap();
+                    request_paint(&event_subsystem);
+                }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Up),
+                    scancode: Some(Scancode::Slash),
                     ..
                 } => {
-                    if rooms[room_index].nav_north > 0 {
-                        room_index = (rooms[room_index].nav_north - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
-                        request_paint(&event_subsystem)
+                    // / starts typing a query to filter rooms by, see query::parse()
+                    query_input = Some(String::new());
+                    video_subsystem.text_input().start();
+                    let title = window_title(&query_input, &room_filter);
+                    canvas.window_mut().set_title(&title).unwrap();
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::G),
+                    keymod,
+                    ..
+                } => {
+                    // G goes to the next room matching the filter and Shift+G the previous one,
+                    // wrapping around
+                    let matches = match &room_filter {
+                        Some((_, query)) => query.filter(&dungeon.rooms),
+                        None => continue,
+                    };
+                    let next = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        matches
+                            .iter()
+                            .rev()
+                            .find(|&&i| i < room_index)
+                            .or_else(|| matches.last())
+                    } else {
+                        matches
+                            .iter()
+                            .find(|&&i| i > room_index)
+                            .or_else(|| matches.first())
+                    };
+                    if let Some(&next) = next {
+                        room_index = next;
+                        room_text.update(&dungeon.rooms[room_index].name);
+                        request_paint(&event_subsystem);
                     }
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Down),
+                    scancode:
+                        Some(
+                            scancode @ (Scancode::Up
+                            | Scancode::Right
+                            | Scancode::Down
+                            | Scancode::Left
+                            | Scancode::PageUp
+                            | Scancode::PageDown),
+                        ),
+                    keymod,
                     ..
                 } => {
-                    if rooms[room_index].nav_south > 0 {
-                        room_index = (rooms[room_index].nav_south - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
+                    let direction = match scancode {
+                        Scancode::Up => rms::Direction::North,
+                        Scancode::Right => rms::Direction::East,
+                        Scancode::Down => rms::Direction::South,
+                        Scancode::Left => rms::Direction::West,
+                        Scancode::PageUp => rms::Direction::Up,
+                        _ => rms::Direction::Down,
+                    };
+                    if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                        // Ctrl+direction removes the exit from both sides
+                        rms::unlink_rooms(&mut dungeon.rooms, room_index, direction);
+                        issues = validate_dungeon(&dungeon.rooms);
+                        println!(
+                            "Removed {} exit of room {}",
+                            direction.name(),
+                            room_index + 1
+                        );
+                    } else if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        // Shift+direction links to the marked room, both ways
+                        if let Some(marked) = marked_room {
+                            rms::link_rooms(&mut dungeon.rooms, room_index, direction, marked);
+                            issues = validate_dungeon(&dungeon.rooms);
+                            println!(
+                                "Linked room {} {} to room {}",
+                                room_index + 1,
+                                direction.name(),
+                                marked + 1
+                            );
+                        }
+                    } else if let Some(target) = dungeon.rooms[room_index]
+                        .link(direction)
+                        .filter(|&target| target < dungeon.rooms.len())
+                    {
+                        room_index = target;
+                        room_text.update(&dungeon.rooms[room_index].name);
                         request_paint(&event_subsystem)
                     }
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Right),
+                    scancode: Some(Scancode::C),
+                    keymod,
+                    ..
+                } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                    // Copy the room as text so it can be pasted into another editor's dungeon
+                    let text = rms_text::room_to_text(room_index + 1, &dungeon.rooms[room_index]);
+                    match video_subsystem.clipboard().set_clipboard_text(&text) {
+                        Ok(()) => println!("Copied room {}", room_index + 1),
+                        Err(e) => eprintln!("rmsedit: can't copy: {}", e),
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::V),
+                    keymod,
+                    ..
+                } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                    // Ctrl+V replaces the current room but keeps its exits, Ctrl+Shift+V takes the
+                    // copied room's exits too. Press N first to paste into a new room.
+                    let with_links = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let room = video_subsystem
+                        .clipboard()
+                        .clipboard_text()
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| {
+                            rms_text::room_from_text("clipboard", &text).map_err(|e| e.to_string())
+                        });
+                    match room {
+                        Ok(room) => {
+                            rms::paste_room(&mut dungeon.rooms, room_index, &room, with_links);
+                            println!("Pasted into room {}", room_index + 1);
+                            room_text.update(&dungeon.rooms[room_index].name);
+                            issues = validate_dungeon(&dungeon.rooms);
+                            request_paint(&event_subsystem);
+                        }
+                        Err(e) => eprintln!("rmsedit: can't paste: {}", e),
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::C),
                     ..
                 } => {
-                    if rooms[room_index].nav_east > 0 {
-                        room_index = (rooms[room_index].nav_east - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
+                    // Ladders lead either up or down, never both
+                    let room = &dungeon.rooms[room_index];
+                    if let Some(target) = room
+                        .link(rms::Direction::Up)
+                        .or_else(|| room.link(rms::Direction::Down))
+                        .filter(|&target| target < dungeon.rooms.len())
+                    {
+                        room_index = target;
+                        room_text.update(&dungeon.rooms[room_index].name);
                         request_paint(&event_subsystem)
                     }
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Left),
+                    scancode: Some(Scancode::Tab),
                     ..
                 } => {
-                    if rooms[room_index].nav_west > 0 {
-                        room_index = (rooms[room_index].nav_west - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
-                        request_paint(&event_subsystem)
+                    show_map = !show_map;
+                    request_paint(&event_subsystem);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::K),
+                    keymod,
+                    ..
+                } => {
+                    // K adds or removes the current room as a key room, Shift+K forgets them all
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        key_rooms.clear();
+                    } else if let Some(i) = key_rooms.iter().position(|&key| key == room_index) {
+                        key_rooms.remove(i);
+                    } else {
+                        key_rooms.push(room_index);
                     }
+                    let numbers: Vec<String> =
+                        key_rooms.iter().map(|key| (key + 1).to_string()).collect();
+                    println!("Key rooms: {}", numbers.join(" "));
+                    for leg in key_rooms.windows(2) {
+                        match graph::route(&dungeon.rooms, leg[0], leg[1]) {
+                            Some(moves) => println!(
+                                "  {} to {}: {} moves ({})",
+                                leg[0] + 1,
+                                leg[1] + 1,
+                                moves.len(),
+                                graph::describe_route(&moves)
+                            ),
+                            None => println!("  {} to {}: no way through", leg[0] + 1, leg[1] + 1),
+                        }
+                    }
+                    request_paint(&event_subsystem);
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::C),
+                    scancode: Some(Scancode::I),
                     ..
                 } => {
-                    if rooms[room_index].nav_up > 0 {
-                        room_index = (rooms[room_index].nav_up - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
-                        request_paint(&event_subsystem)
-                    } else if rooms[room_index].nav_down > 0 {
-                        room_index = (rooms[room_index].nav_down - 1) as usize;
-                        room_text.update(&rooms[room_index].name);
+                    show_stats = !show_stats;
+                    request_paint(&event_subsystem);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::W),
+                    ..
+                } => {
+                    show_walkability = !show_walkability;
+                    request_paint(&event_subsystem);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::M),
+                    ..
+                } => {
+                    marked_room = Some(room_index);
+                    println!("Marked room {} for linking", room_index + 1);
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode @ (Scancode::N | Scancode::D)),
+                    ..
+                } => {
+                    // N adds a blank room, D a copy of the current one
+                    let new_room = if scancode == Scancode::N {
+                        rms::append_room(&mut dungeon.rooms, rms::Room::new("New room"))
+                    } else {
+                        rms::duplicate_room(&mut dungeon.rooms, room_index)
+                    };
+                    match new_room {
+                        Some(new_room) => {
+                            room_index = new_room;
+                            room_text.update(&dungeon.rooms[room_index].name);
+                            issues = validate_dungeon(&dungeon.rooms);
+                            request_paint(&event_subsystem)
+                        }
+                        None => println!("Can't have more than {} rooms", rms::MAX_ROOMS),
+                    }
+                }
+                Event::KeyDown {
+                    scancode:
+                        Some(
+                            scancode @ (Scancode::Comma
+                            | Scancode::Period
+                            | Scancode::Minus
+                            | Scancode::Equals),
+                        ),
+                    ..
+                } => {
+                    // , and . step through the monster list, - and = change how many spawn
+                    let room = &mut dungeon.rooms[room_index];
+                    match scancode {
+                        Scancode::Comma => room.monster_id = room.monster_id.saturating_sub(1),
+                        Scancode::Period if (room.monster_id as usize) < monsters.len() => {
+                            room.monster_id += 1
+                        }
+                        Scancode::Minus => {
+                            room.monster_count = room.monster_count.saturating_sub(1)
+                        }
+                        Scancode::Equals => {
+                            room.monster_count = room.monster_count.saturating_add(1)
+                        }
+                        _ => {}
+                    }
+                    issues = validate_dungeon(&dungeon.rooms);
+                    request_paint(&event_subsystem);
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode @ (Scancode::P | Scancode::E)),
+                    ..
+                } => {
+                    // P places monster spawn points, E erases objects (spawn points included)
+                    brush = Brush::Object(if scancode == Scancode::P {
+                        object::RoomObject::MONSTER_SPAWN
+                    } else {
+                        object::RoomObject::None
+                    });
+                    println!("Brush: {}", brush);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::T),
+                    ..
+                } => {
+                    // T switches the brush to a trap, then steps through the kinds of trap
+                    brush = Brush::Tile(rms::Tile::Trap(match brush {
+                        Brush::Tile(rms::Tile::Trap(kind)) => kind.next(),
+                        _ => rms::TrapKind::default(),
+                    }));
+                    println!("Brush: {}", brush);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::O),
+                    ..
+                } => {
+                    // O switches the brush to an object, then steps through the objects. Nothing
+                    // comes after the last object, to erase with.
+                    brush = Brush::Object(match brush {
+                        Brush::Object(object) => object.next(),
+                        _ => object::RoomObject::PLACEABLE[0],
+                    });
+                    println!("Brush: {}", brush);
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::R),
+                    keymod,
+                    ..
+                } => {
+                    // R replaces whatever is under the mouse with the brush in this room, Shift+R
+                    // on this floor and Ctrl+R everywhere. The first press shows what would
+                    // change, the second makes the change.
+                    let (room_x, room_y) = editor_view.localize(mouse_position.0, mouse_position.1);
+                    if show_map
+                        || show_stats
+                        || room_x < 0
+                        || room_y < 0
+                        || room_x >= rms::ROOM_WIDTH as i32
+                        || room_y >= rms::ROOM_HEIGHT as i32
+                    {
+                        continue;
+                    }
+                    let room = &dungeon.rooms[room_index];
+                    let replacement = match brush {
+                        Brush::Tile(to) => replace::Replacement::Tile {
+                            from: room.get_tile(room_x as u32, room_y as u32),
+                            to,
+                        },
+                        Brush::Object(to) => replace::Replacement::Object {
+                            from: room.get_object(room_x as u32, room_y as u32),
+                            to,
+                        },
+                    };
+                    let rooms = if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                        None
+                    } else if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        let map_layout = layout::layout(&dungeon.rooms, validate::STARTING_ROOM);
+                        let floor = map_layout.positions[room_index].floor;
+                        Some(
+                            (0..dungeon.rooms.len())
+                                .filter(|&room| map_layout.positions[room].floor == floor)
+                                .collect(),
+                        )
+                    } else {
+                        Some(vec![room_index])
+                    };
+                    let scope = replace::Scope {
+                        rooms,
+                        region: replace::Region::ALL,
+                    };
+
+                    let pending = Some((replacement, scope.clone()));
+                    if pending_replace == pending {
+                        let changes = replace::replace(&mut dungeon.rooms, replacement, &scope);
+                        print!(
+                            "Replaced {}\n{}",
+                            replacement,
+                            replace::summarize(&dungeon.rooms, &changes)
+                        );
+                        issues = validate_dungeon(&dungeon.rooms);
+                        request_paint(&event_subsystem);
+                    } else {
+                        let changes = replace::preview(&dungeon.rooms, replacement, &scope);
+                        print!(
+                            "Replace {}?\n{}Press R again the same way to replace\n",
+                            replacement,
+                            replace::summarize(&dungeon.rooms, &changes)
+                        );
+                        pending_replace = pending;
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(Scancode::Delete),
+                    ..
+                } => {
+                    if dungeon.rooms.len() > 1 {
+                        rms::delete_room(&mut dungeon.rooms, room_index);
+                        println!("Deleted room {}", room_index + 1);
+                        room_index = room_index.min(dungeon.rooms.len() - 1);
+                        // Room numbers have shifted
+                        marked_room = None;
+                        key_rooms.clear();
+                        room_text.update(&dungeon.rooms[room_index].name);
+                        issues = validate_dungeon(&dungeon.rooms);
                         request_paint(&event_subsystem)
                     }
                 }
+                Event::KeyDown {
+                    scancode: Some(scancode @ (Scancode::LeftBracket | Scancode::RightBracket)),
+                    ..
+                } => {
+                    // [ and ] move the current room earlier or later in the file
+                    let target = if scancode == Scancode::LeftBracket {
+                        room_index.checked_sub(1)
+                    } else {
+                        Some(room_index + 1).filter(|&target| target < dungeon.rooms.len())
+                    };
+                    if let Some(target) = target {
+                        rms::move_room(&mut dungeon.rooms, room_index, target);
+                        room_index = target;
+                        marked_room = None;
+                        key_rooms.clear();
+                        issues = validate_dungeon(&dungeon.rooms);
+                        println!("Moved room to {}", room_index + 1);
+                    }
+                }
                 Event::KeyDown {
                     scancode: Some(Scancode::S),
                     ..
                 } => {
                     // TODO: This is a temporary filename for testing
-                    rms::save_rooms(&rooms, "TEST.RMS").unwrap();
+                    if let Err(e) = rms::save_rooms(&dungeon, "TEST.RMS") {
+                        eprintln!("rmsedit: {}", e);
+                        continue;
+                    }
                     // TODO: This is also temporary to ease copy-pasting and loading in EXPLORER.EXE
                     std::fs::write("TEST.OVR", "Made with rmsedit").unwrap();
                     println!("Saved to TEST.RMS and TEST.OVR");
@@ -251,6 +812,42 @@ fn main() {
                     y,
                     ..
                 } => {
+                    if show_map {
+                        // Clicking a room on the map opens it
+                        let map_layout = layout::layout(&dungeon.rooms, validate::STARTING_ROOM);
+                        let center = map_layout.positions[room_index];
+                        if let Some(clicked) = map_layout.positions.iter().position(|&position| {
+                            position.floor == center.floor
+                                && map_view.room_rect(position, center).contains_point((x, y))
+                        }) {
+                            room_index = clicked;
+                            room_text.update(&dungeon.rooms[room_index].name);
+                            show_map = false;
+                            request_paint(&event_subsystem);
+                        }
+                        continue;
+                    }
+                    if show_stats {
+                        // The stats cover the room, so a click only closes them
+                        show_stats = false;
+                        request_paint(&event_subsystem);
+                        continue;
+                    }
+
+                    // Clicking a monster makes it the room's monster
+                    let (monster_x, monster_y) = monster_view.localize(x, y);
+                    let monster_index = monster_y * MONSTER_VIEW_COLUMNS as i32 + monster_x;
+                    if monster_x >= 0
+                        && monster_y >= 0
+                        && monster_x < MONSTER_VIEW_COLUMNS as i32
+                        && monster_index < monsters.len() as i32
+                    {
+                        dungeon.rooms[room_index].monster_id = monster_index as u8 + 1;
+                        issues = validate_dungeon(&dungeon.rooms);
+                        request_paint(&event_subsystem);
+                        continue;
+                    }
+
                     is_dragging = true;
                     let (room_x, room_y) = editor_view.localize(x, y);
                     if room_x >= 0
@@ -258,10 +855,31 @@ fn main() {
                         && room_x < rms::ROOM_WIDTH as i32
                         && room_y < rms::ROOM_HEIGHT as i32
                     {
-                        rooms[room_index].set_tile(room_x as u32, room_y as u32, 1);
+                        brush.paint(&mut dungeon.rooms[room_index], room_x as u32, room_y as u32);
+                        issues = validate_dungeon(&dungeon.rooms);
                         request_paint(&event_subsystem);
                     }
                 }
+                Event::MouseButtonDown {
+                    mouse_btn: sdl2::mouse::MouseButton::Right,
+                    x,
+                    y,
+                    ..
+                } => {
+                    // Right click picks up the clicked tile or object as the brush
+                    let (room_x, room_y) = editor_view.localize(x, y);
+                    if !show_map
+                        && !show_stats
+                        && room_x >= 0
+                        && room_y >= 0
+                        && room_x < rms::ROOM_WIDTH as i32
+                        && room_y < rms::ROOM_HEIGHT as i32
+                    {
+                        brush =
+                            Brush::pick(&dungeon.rooms[room_index], room_x as u32, room_y as u32);
+                        println!("Brush: {}", brush);
+                    }
+                }
                 Event::MouseButtonUp {
                     mouse_btn: sdl2::mouse::MouseButton::Left,
                     ..
@@ -269,7 +887,8 @@ fn main() {
                     is_dragging = false;
                 }
                 Event::MouseMotion { x, y, .. } => {
-                    if !is_dragging {
+                    mouse_position = (x, y);
+                    if !is_dragging || show_stats {
                         break;
                     }
                     let (room_x, room_y) = editor_view.localize(x, y);
@@ -278,7 +897,8 @@ fn main() {
                         && room_x < rms::ROOM_WIDTH as i32
                         && room_y < rms::ROOM_HEIGHT as i32
                     {
-                        rooms[room_index].set_tile(room_x as u32, room_y as u32, 1);
+                        brush.paint(&mut dungeon.rooms[room_index], room_x as u32, room_y as u32);
+                        issues = validate_dungeon(&dungeon.rooms);
                         request_paint(&event_subsystem);
                     }
                 }
@@ -291,47 +911,338 @@ fn main() {
                     // But I know under the covers that it's just a User event, which works better
                     // in this match statement.
                     canvas.clear();
-                    for y in 0..rms::ROOM_HEIGHT {
-                        for x in 0..rms::ROOM_WIDTH {
-                            let draw_rect = editor_view.rect(x, y);
+                    if show_stats {
+                        // Recounted every time so that it follows edits
+                        let statistics = stats::statistics(&dungeon.rooms, validate::STARTING_ROOM);
+                        for (i, line) in statistics.summary().iter().enumerate() {
+                            let line = text::TextRendering::from_text(
+                                line,
+                                &Color::RGB(0xFF, 0xFF, 0xFF),
+                                &liberation_sans,
+                                &texture_creator,
+                            );
+                            canvas
+                                .copy(line.texture(), None, line.rect(8, 8 + i as i32 * 24))
+                                .unwrap();
+                        }
+                        canvas.present();
+                        continue;
+                    }
+                    if show_map {
+                        let map_layout = layout::layout(&dungeon.rooms, validate::STARTING_ROOM);
+                        let center = map_layout.positions[room_index];
+                        let on_floor = |room: rms::RoomIndex| {
+                            room < dungeon.rooms.len()
+                                && map_layout.positions[room].floor == center.floor
+                        };
 
-                            let mut tile = rooms[room_index].get_tile(x, y);
-                            if tile > 0 {
-                                tile -= 1;
+                        // Links first so the miniatures cover their ends
+                        for (i, room) in dungeon
+                            .rooms
+                            .iter()
+                            .enumerate()
+                            .filter(|&(i, _)| on_floor(i))
+                        {
+                            for &direction in rms::Direction::ALL[..4].iter() {
+                                let target = match room.link(direction).filter(|&t| on_floor(t)) {
+                                    Some(target) => target,
+                                    None => continue,
+                                };
+                                let is_contradiction =
+                                    map_layout.contradictions.contains(&(i, direction))
+                                        || map_layout
+                                            .contradictions
+                                            .contains(&(target, direction.opposite()));
+                                canvas.set_draw_color(if is_contradiction {
+                                    Color::RGB(0xFF, 0x55, 0x55)
+                                } else {
+                                    Color::RGB(0xAA, 0xAA, 0xAA)
+                                });
                                 canvas
-                                    .copy(&tiles_atlas[tile as usize], None, draw_rect)
+                                    .draw_line(
+                                        map_view
+                                            .room_rect(map_layout.positions[i], center)
+                                            .center(),
+                                        map_view
+                                            .room_rect(map_layout.positions[target], center)
+                                            .center(),
+                                    )
                                     .unwrap();
                             }
+                        }
+
+                        let filter_matches = match &room_filter {
+                            Some((_, query)) => query.filter(&dungeon.rooms),
+                            None => Vec::new(),
+                        };
+                        for (i, room) in dungeon
+                            .rooms
+                            .iter()
+                            .enumerate()
+                            .filter(|&(i, _)| on_floor(i))
+                        {
+                            let room_rect = map_view.room_rect(map_layout.positions[i], center);
+                            for y in 0..rms::ROOM_HEIGHT {
+                                for x in 0..rms::ROOM_WIDTH {
+                                    // Tiles past the end of a short sheet are left out, like
+                                    // sprites::cell_sprites() does
+                                    let graphic = room.get_tile(x, y).graphic() as usize;
+                                    if let Some(texture) = graphic
+                                        .checked_sub(1)
+                                        .and_then(|graphic| tiles_atlas.get(graphic))
+                                    {
+                                        canvas
+                                            .copy(
+                                                texture,
+                                                None,
+                                                map_view.tile_rect(room_rect, x, y),
+                                            )
+                                            .unwrap();
+                                    }
+                                }
+                            }
+                            // Ladders up are marked top left, ladders down top right
+                            for &(direction, corner) in [
+                                (rms::Direction::Up, room_rect.top_left()),
+                                (rms::Direction::Down, room_rect.top_right()),
+                            ]
+                            .iter()
+                            {
+                                if room.link(direction).is_some() {
+                                    draw_marker(
+                                        &mut canvas,
+                                        &mut markers,
+                                        if direction == rms::Direction::Up {
+                                            'U'
+                                        } else {
+                                            'D'
+                                        },
+                                        Color::RGB(0x55, 0xFF, 0xFF),
+                                        &liberation_sans,
+                                        &texture_creator,
+                                        Rect::from_center(corner, 1, 1),
+                                    );
+                                }
+                            }
+                            let outline = if i == room_index {
+                                Some(Color::RGB(0xFF, 0xFF, 0x55))
+                            } else if key_rooms.contains(&i) {
+                                Some(Color::RGB(0x55, 0xFF, 0xFF))
+                            } else if filter_matches.contains(&i) {
+                                Some(Color::RGB(0x55, 0xFF, 0x55))
+                            } else if map_layout.is_contradicted(&dungeon.rooms, i) {
+                                Some(Color::RGB(0xFF, 0x55, 0x55))
+                            } else {
+                                None
+                            };
+                            if let Some(outline) = outline {
+                                canvas.set_draw_color(outline);
+                                canvas.draw_rect(room_rect).unwrap();
+                            }
+                        }
 
-                            match rooms[room_index].get_object_type(x, y) {
-                                rms::ObjectType::Monster => {
-                                    let monster_id = rooms[room_index].monster_id - 1;
-                                    tile = monsters[monster_id as usize].gfx_id - 1;
-                                    canvas
-                                        .copy(&monsters_atlas[tile as usize], None, draw_rect)
-                                        .unwrap();
+                        // The route through the key rooms goes over everything else
+                        canvas.set_draw_color(Color::RGB(0x55, 0xFF, 0xFF));
+                        for leg in key_rooms.windows(2) {
+                            let moves = graph::route(&dungeon.rooms, leg[0], leg[1]);
+                            let mut from = leg[0];
+                            for &(_, to) in moves.iter().flatten() {
+                                if on_floor(from) && on_floor(to) {
+                                    let start = map_view
+                                        .room_rect(map_layout.positions[from], center)
+                                        .center();
+                                    let end = map_view
+                                        .room_rect(map_layout.positions[to], center)
+                                        .center();
+                                    // Thicker than the links underneath
+                                    for offset in -1..=1 {
+                                        canvas
+                                            .draw_line(
+                                                start.offset(offset, offset),
+                                                end.offset(offset, offset),
+                                            )
+                                            .unwrap();
+                                    }
                                 }
-                                rms::ObjectType::Object => {
-                                    tile = rooms[room_index].get_object(x, y);
-                                    if tile == 0 {
-                                        continue;
+                                from = to;
+                            }
+                        }
+                        canvas.set_draw_color(Color::RGB(0x00, 0x00, 0x00));
+
+                        let floors = map_layout.floors();
+                        let distance =
+                            graph::distances_from(&dungeon.rooms, validate::STARTING_ROOM)
+                                [room_index]
+                                .map_or(String::from("unreachable"), |distance| {
+                                    format!("{} moves from the entrance", distance)
+                                });
+                        let summary = format!(
+                            "Floor {} of {}, room {}: {} ({})",
+                            floors
+                                .iter()
+                                .position(|&floor| floor == center.floor)
+                                .unwrap()
+                                + 1,
+                            floors.len(),
+                            room_index + 1,
+                            dungeon.rooms[room_index].name,
+                            distance
+                        );
+                        if summary != map_summary {
+                            map_text.update(&summary);
+                            map_summary = summary;
+                        }
+                        canvas
+                            .copy(map_text.texture(), None, map_text.rect(4, 452))
+                            .unwrap();
+                        canvas.present();
+                        continue;
+                    }
+                    for y in 0..rms::ROOM_HEIGHT {
+                        for x in 0..rms::ROOM_WIDTH {
+                            let draw_rect = editor_view.rect(x, y);
+
+                            for sprite in sprites::cell_sprites(
+                                &dungeon.rooms[room_index],
+                                &monsters,
+                                tiles_atlas.len(),
+                                monsters_atlas.len(),
+                                x,
+                                y,
+                            ) {
+                                match sprite {
+                                    sprites::Sprite::Tile(tile) => {
+                                        canvas.copy(&tiles_atlas[tile], None, draw_rect).unwrap()
                                     }
-                                    // TODO: For some reason, I made it so I don't need to -1 here...
-                                    // when I probably should since it's ambiguous whether 0 is the "no tile" sentinel or literally tile 0
-                                    // tile -= 1;
-                                    canvas
-                                        .copy(&tiles_atlas[tile as usize], None, draw_rect)
-                                        .unwrap();
+                                    sprites::Sprite::Monster(monster) => canvas
+                                        .copy(&monsters_atlas[monster], None, draw_rect)
+                                        .unwrap(),
+                                    sprites::Sprite::Marker(character, color) => draw_marker(
+                                        &mut canvas,
+                                        &mut markers,
+                                        character,
+                                        Color::RGBA(color.r, color.g, color.b, color.a),
+                                        &liberation_sans,
+                                        &texture_creator,
+                                        draw_rect,
+                                    ),
+                                }
+                            }
+                        }
+                    }
+
+                    if show_walkability {
+                        // Green where the player can walk from the exits, red where they can't
+                        // and yellow on tiles nobody has worked out, which are taken to be floor.
+                        // Walls and water are left alone.
+                        let room = &dungeon.rooms[room_index];
+                        let walkability = walk::optimistic_walkability(room);
+                        canvas.set_blend_mode(BlendMode::Blend);
+                        for y in 0..rms::ROOM_HEIGHT {
+                            for x in 0..rms::ROOM_WIDTH {
+                                if walkability.area(x, y).is_none() {
+                                    continue;
                                 }
-                                _ => {}
+                                let passability = walk::cell_passability(room, x, y);
+                                canvas.set_draw_color(
+                                    if passability == walk::Passability::Unknown {
+                                        Color::RGBA(0xFF, 0xFF, 0x55, 0x60)
+                                    } else if walkability.is_reachable(x, y) {
+                                        Color::RGBA(0x55, 0xFF, 0x55, 0x60)
+                                    } else {
+                                        Color::RGBA(0xFF, 0x55, 0x55, 0x60)
+                                    },
+                                );
+                                canvas.fill_rect(editor_view.rect(x, y)).unwrap();
                             }
                         }
+                        canvas.set_draw_color(Color::RGB(0xFF, 0x55, 0x55));
+                        for &(x, y) in walkability.unreachable_spots.iter() {
+                            canvas.draw_rect(editor_view.rect(x, y)).unwrap();
+                        }
+                        canvas.set_blend_mode(BlendMode::None);
+                        canvas.set_draw_color(Color::RGB(0x00, 0x00, 0x00));
+                    }
+
+                    // A strip along each edge shows the neighbour's facing edge: light where it's
+                    // open, dark where it's a wall and red where it doesn't line up with this room
+                    for &direction in rms::Direction::ALL[..4].iter() {
+                        let mismatches =
+                            match walk::edge_mismatches(&dungeon.rooms, room_index, direction) {
+                                Some(mismatches) => mismatches,
+                                None => continue,
+                            };
+                        let neighbour =
+                            &dungeon.rooms[dungeon.rooms[room_index].link(direction).unwrap()];
+                        let openings = walk::edge_openings(neighbour, direction.opposite());
+                        for (i, (x, y)) in walk::edge_cells(direction).into_iter().enumerate() {
+                            let cell = editor_view.rect(x, y);
+                            let strip = match direction {
+                                rms::Direction::North => {
+                                    Rect::new(cell.x(), cell.y() - 8, cell.width(), 6)
+                                }
+                                rms::Direction::South => {
+                                    Rect::new(cell.x(), cell.bottom() + 2, cell.width(), 6)
+                                }
+                                rms::Direction::West => {
+                                    Rect::new(cell.x() - 8, cell.y(), 6, cell.height())
+                                }
+                                _ => Rect::new(cell.right() + 2, cell.y(), 6, cell.height()),
+                            };
+                            canvas.set_draw_color(if mismatches.contains(&(i as u32)) {
+                                Color::RGB(0xFF, 0x55, 0x55)
+                            } else if openings[i] {
+                                Color::RGB(0xAA, 0xAA, 0xAA)
+                            } else {
+                                Color::RGB(0x55, 0x55, 0x55)
+                            });
+                            canvas.fill_rect(strip).unwrap();
+                        }
                     }
+                    canvas.set_draw_color(Color::RGB(0x00, 0x00, 0x00));
 
                     canvas
                         .copy(&room_text.texture(), None, room_text.rect(4, 300))
                         .unwrap();
 
+                    // Only rerender when the summary changes since this runs on every event
+                    let summary = validate::summarize(&issues, room_index);
+                    if summary != issues_summary {
+                        issues_text.update(&summary);
+                        issues_summary = summary;
+                    }
+                    canvas
+                        .copy(issues_text.texture(), None, issues_text.rect(4, 324))
+                        .unwrap();
+
+                    let summary = summarize_monsters(&dungeon.rooms[room_index]);
+                    if summary != monster_summary {
+                        monster_text.update(&summary);
+                        monster_summary = summary;
+                    }
+                    canvas
+                        .copy(monster_text.texture(), None, monster_text.rect(4, 348))
+                        .unwrap();
+
+                    for (i, monster) in monsters.iter().enumerate() {
+                        let draw_rect = monster_view.rect(
+                            i as u32 % MONSTER_VIEW_COLUMNS,
+                            i as u32 / MONSTER_VIEW_COLUMNS,
+                        );
+                        if let Some(sprite) = (monster.gfx_id as usize)
+                            .checked_sub(1)
+                            .and_then(|sprite| monsters_atlas.get(sprite))
+                        {
+                            canvas.copy(sprite, None, draw_rect).unwrap();
+                        }
+                        // Outline the current room's monster
+                        if dungeon.rooms[room_index].monster_index() == Some(i) {
+                            canvas.set_draw_color(Color::RGB(0xFF, 0xFF, 0x55));
+                            canvas.draw_rect(draw_rect).unwrap();
+                            canvas.set_draw_color(Color::RGB(0x00, 0x00, 0x00));
+                        }
+                    }
+
                     canvas.present();
                 }
                 // Convert all other events into paint events (keep the screen fresh)
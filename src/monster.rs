@@ -1,3 +1,6 @@
+use super::error;
+use super::error::Error;
+
 pub struct Monster {
     pub gfx_id: u8, // TODO other fields
 }
@@ -5,12 +8,24 @@ pub struct Monster {
 const MONSTER_RECORD_SIZE: usize = 0x1F;
 const MONSTER_RECORD_GFX_ID_OFFEST: usize = 0x16;
 
-pub fn load_monsters(filename: &str) -> Vec<Monster> {
-    let mon_dat = std::fs::read(filename).unwrap();
-    return mon_dat
+pub fn load_monsters(filename: &str) -> error::Result<Vec<Monster>> {
+    let mon_dat = std::fs::read(filename).map_err(|e| Error::io(filename, e))?;
+    mon_dat
         .chunks(MONSTER_RECORD_SIZE)
-        .map(|x| Monster {
-            gfx_id: x[MONSTER_RECORD_GFX_ID_OFFEST],
+        .enumerate()
+        .map(|(i, x)| {
+            if x.len() < MONSTER_RECORD_SIZE {
+                return Err(Error::TruncatedRecord {
+                    filename: filename.to_string(),
+                    record: i,
+                    offset: i * MONSTER_RECORD_SIZE,
+                    expected: MONSTER_RECORD_SIZE,
+                    actual: x.len(),
+                });
+            }
+            Ok(Monster {
+                gfx_id: x[MONSTER_RECORD_GFX_ID_OFFEST],
+            })
         })
-        .collect();
+        .collect()
 }
@@ -0,0 +1,195 @@
+use std::fmt;
+
+/// What a cell of a room's object layer holds. Objects are stored as ASCII letters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RoomObject {
+    None,
+    /// Where one of the room's monsters appears. Anything up to 'c' is a monster; the exact letter
+    /// is kept so it can be saved back unchanged.
+    Monster(u8),
+    MagicalDarkness,
+    TreasureChest,
+    Smoke,
+    MovableBlock,
+    DoorVertical,
+    DoorHorizontal,
+    FunnyLookingChest,
+    SoftSectionOfWall,
+    SoftPieceOfWall,
+    SoftPileOfRubble,
+    OldBody,
+    OldBones,
+    OldStoneCoffin,
+    OldGrave,
+    MovableGlassBlock,
+    /// The game has two skeleton objects that describe themselves the same way.
+    OldSkeleton,
+    OldSkeletonAlt,
+    HollowObelisk,
+    Blood,
+    StoneMarker,
+    /// A letter the game doesn't know about (as far as we know)
+    Unknown(u8),
+}
+
+/// Everything from 1 up to this is a monster.
+const LAST_MONSTER_CODE: u8 = b'c';
+
+impl RoomObject {
+    /// What the editor places for new monster spawn points.
+    pub const MONSTER_SPAWN: RoomObject = RoomObject::Monster(b'a');
+
+    /// Every object that isn't a monster, in code order. Used to cycle through objects while
+    /// painting.
+    pub const PLACEABLE: [RoomObject; 20] = [
+        RoomObject::MagicalDarkness,
+        RoomObject::TreasureChest,
+        RoomObject::Smoke,
+        RoomObject::MovableBlock,
+        RoomObject::DoorVertical,
+        RoomObject::DoorHorizontal,
+        RoomObject::FunnyLookingChest,
+        RoomObject::SoftSectionOfWall,
+        RoomObject::SoftPieceOfWall,
+        RoomObject::SoftPileOfRubble,
+        RoomObject::OldBody,
+        RoomObject::OldBones,
+        RoomObject::OldStoneCoffin,
+        RoomObject::OldGrave,
+        RoomObject::MovableGlassBlock,
+        RoomObject::OldSkeleton,
+        RoomObject::OldSkeletonAlt,
+        RoomObject::HollowObelisk,
+        RoomObject::Blood,
+        RoomObject::StoneMarker,
+    ];
+
+    pub fn from_byte(code: u8) -> RoomObject {
+        match code {
+            0 => RoomObject::None,
+            1..=LAST_MONSTER_CODE => RoomObject::Monster(code),
+            b'd' => RoomObject::MagicalDarkness,
+            b'e' => RoomObject::TreasureChest,
+            b'f' => RoomObject::Smoke,
+            b'g' => RoomObject::MovableBlock,
+            b'h' => RoomObject::DoorVertical,
+            b'i' => RoomObject::DoorHorizontal,
+            b'j' => RoomObject::FunnyLookingChest,
+            b'k' => RoomObject::SoftSectionOfWall,
+            b'l' => RoomObject::SoftPieceOfWall,
+            b'm' => RoomObject::SoftPileOfRubble,
+            b'n' => RoomObject::OldBody,
+            b'o' => RoomObject::OldBones,
+            b'p' => RoomObject::OldStoneCoffin,
+            b'q' => RoomObject::OldGrave,
+            b'r' => RoomObject::MovableGlassBlock,
+            b's' => RoomObject::OldSkeleton,
+            b't' => RoomObject::OldSkeletonAlt,
+            b'u' => RoomObject::HollowObelisk,
+            b'v' => RoomObject::Blood,
+            b'w' => RoomObject::StoneMarker,
+            _ => RoomObject::Unknown(code),
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            RoomObject::None => 0,
+            RoomObject::Monster(code) => code,
+            RoomObject::MagicalDarkness => b'd',
+            RoomObject::TreasureChest => b'e',
+            RoomObject::Smoke => b'f',
+            RoomObject::MovableBlock => b'g',
+            RoomObject::DoorVertical => b'h',
+            RoomObject::DoorHorizontal => b'i',
+            RoomObject::FunnyLookingChest => b'j',
+            RoomObject::SoftSectionOfWall => b'k',
+            RoomObject::SoftPieceOfWall => b'l',
+            RoomObject::SoftPileOfRubble => b'm',
+            RoomObject::OldBody => b'n',
+            RoomObject::OldBones => b'o',
+            RoomObject::OldStoneCoffin => b'p',
+            RoomObject::OldGrave => b'q',
+            RoomObject::MovableGlassBlock => b'r',
+            RoomObject::OldSkeleton => b's',
+            RoomObject::OldSkeletonAlt => b't',
+            RoomObject::HollowObelisk => b'u',
+            RoomObject::Blood => b'v',
+            RoomObject::StoneMarker => b'w',
+            RoomObject::Unknown(code) => code,
+        }
+    }
+
+    /// How the game describes the object (more or less).
+    pub fn name(self) -> &'static str {
+        match self {
+            RoomObject::None => "Nothing",
+            RoomObject::Monster(_) => "Monster",
+            RoomObject::MagicalDarkness => "Magical darkness",
+            RoomObject::TreasureChest => "Treasure chest",
+            RoomObject::Smoke => "Smoke",
+            RoomObject::MovableBlock => "Movable block",
+            RoomObject::DoorVertical => "Door (vertical)",
+            RoomObject::DoorHorizontal => "Door (horizontal)",
+            RoomObject::FunnyLookingChest => "Funny looking chest",
+            RoomObject::SoftSectionOfWall => "Soft section of wall",
+            RoomObject::SoftPieceOfWall => "Soft piece of wall",
+            RoomObject::SoftPileOfRubble => "Soft pile of rubble",
+            RoomObject::OldBody => "Old body",
+            RoomObject::OldBones => "Old bones",
+            RoomObject::OldStoneCoffin => "Old stone coffin",
+            RoomObject::OldGrave => "Old grave",
+            RoomObject::MovableGlassBlock => "Movable glass block",
+            RoomObject::OldSkeleton => "Old skeleton",
+            RoomObject::OldSkeletonAlt => "Old skeleton (alternate)",
+            RoomObject::HollowObelisk => "Hollow obelisk",
+            RoomObject::Blood => "Just some blood",
+            RoomObject::StoneMarker => "Stone marker",
+            RoomObject::Unknown(_) => "Unknown",
+        }
+    }
+
+    /// The 1-based EGAPICS tile to draw, if we know which one it is. Monsters are drawn from
+    /// PYMON.PIC instead so they don't have one either.
+    pub fn sprite(self) -> Option<u8> {
+        match self {
+            RoomObject::MagicalDarkness => Some(48),
+            RoomObject::TreasureChest => Some(22),
+            RoomObject::Smoke => Some(47),
+            RoomObject::MovableBlock => Some(30),
+            RoomObject::DoorVertical => Some(38),
+            RoomObject::DoorHorizontal => Some(37),
+            RoomObject::SoftPieceOfWall => Some(43),
+            RoomObject::SoftPileOfRubble => Some(19),
+            RoomObject::OldBody => Some(23),
+            RoomObject::OldBones => Some(18),
+            RoomObject::OldStoneCoffin => Some(50),
+            RoomObject::OldGrave => Some(55),
+            RoomObject::Blood => Some(83),
+            // Nobody has matched the rest to an EGAPICS tile, and a wrong picture would be worse
+            // than none, so they're drawn as their letter (see sprites::cell_sprites())
+            _ => None,
+        }
+    }
+
+    /// The object after this one in PLACEABLE, wrapping around to None after the last one.
+    pub fn next(self) -> RoomObject {
+        let position = RoomObject::PLACEABLE
+            .iter()
+            .position(|&object| object == self);
+        match position {
+            None => RoomObject::PLACEABLE[0],
+            Some(i) if i + 1 < RoomObject::PLACEABLE.len() => RoomObject::PLACEABLE[i + 1],
+            Some(_) => RoomObject::None,
+        }
+    }
+}
+
+impl fmt::Display for RoomObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoomObject::None => write!(f, "{}", self.name()),
+            _ => write!(f, "{} ('{}')", self.name(), self.to_byte() as char),
+        }
+    }
+}
@@ -1,24 +1,70 @@
+use std::fmt;
+
+use super::cp437;
+
+/// Why a Rust string can't be stored as a Pascal string.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PascalError {
+    /// Code page 437 doesn't have this character.
+    Unrepresentable(char),
+    /// The string has `length` characters but only `max_length` fit.
+    TooLong { length: usize, max_length: u8 },
+}
+
+impl fmt::Display for PascalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PascalError::Unrepresentable(c) => {
+                write!(f, "{:?} isn't in the DOS character set", c)
+            }
+            PascalError::TooLong { length, max_length } => write!(
+                f,
+                "{} characters is longer than the {} that fit",
+                length, max_length
+            ),
+        }
+    }
+}
+
 /// Convert a byte length-prefixed string into a Rust string.
 /// Only single byte lengths are supported (max 255 chars)
 ///
+/// The contents are decoded as DOS code page 437, which is what the game displays. Returns `None`
+/// if the length prefix runs past the end of `pstring`.
+///
 /// https://en.wikipedia.org/wiki/String_(computer_science)#Length-prefixed
-pub fn from_pascal_string(pstring: &[u8]) -> String {
-    let length = pstring[0] as usize;
-    String::from_utf8(pstring[1..length + 1].to_vec()).unwrap()
+pub fn from_pascal_string(pstring: &[u8]) -> Option<String> {
+    let length = *pstring.first()? as usize;
+    let contents = pstring.get(1..length + 1)?;
+    Some(cp437::decode(contents))
 }
 
-/// Convert a Rust string of at most 255 chars to a Pascal String.
-/// Will truncate if `data.len() > max_length`.
+/// Convert a Rust string of at most `max_length` chars to a code page 437 Pascal String.
 ///
 /// https://en.wikipedia.org/wiki/String_(computer_science)#Length-prefixed
-pub fn to_pascal_string(data: &str, max_length: u8) -> Vec<u8> {
-    // TODO: Warn if str.len() > 255?
-    let length = if data.len() > max_length as usize {
-        max_length
-    } else {
-        data.len() as u8
-    };
-    let mut builder = vec![length];
-    builder.extend_from_slice(&data.as_bytes()[0..length as usize]);
-    return builder;
+pub fn to_pascal_string(data: &str, max_length: u8) -> Result<Vec<u8>, PascalError> {
+    let contents = cp437::encode(data).map_err(PascalError::Unrepresentable)?;
+    if contents.len() > max_length as usize {
+        return Err(PascalError::TooLong {
+            length: contents.len(),
+            max_length,
+        });
+    }
+    let mut builder = vec![contents.len() as u8];
+    builder.extend_from_slice(&contents);
+    Ok(builder)
+}
+
+/// Like to_pascal_string() but always succeeds: characters that code page 437 doesn't have become
+/// '?' and the string is cut short at `max_length`. Check with to_pascal_string() first to tell
+/// the user what was lost.
+pub fn to_pascal_string_lossy(data: &str, max_length: u8) -> Vec<u8> {
+    let contents: Vec<u8> = data
+        .chars()
+        .map(|c| cp437::encode_char(c).unwrap_or(b'?'))
+        .take(max_length as usize)
+        .collect();
+    let mut builder = vec![contents.len() as u8];
+    builder.extend_from_slice(&contents);
+    builder
 }
@@ -0,0 +1,667 @@
+use std::fmt;
+use std::path::Path;
+
+use super::analysis::UNKNOWN_FIELDS;
+use super::diff;
+use super::diff::{Change, DungeonDiff, RoomDiff};
+use super::error;
+use super::error::Error;
+use super::json::Value;
+use super::rms;
+use super::rms::{Dungeon, Room, RoomIndex};
+use super::rms_json::{hex, Reader};
+
+/// Identifies our JSON documents, in case other tools grow their own.
+const FORMAT: &str = "rmsedit patch";
+const VERSION: i64 = 1;
+
+/// A run of bytes that differs in a file the editor doesn't understand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ByteChange {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    /// Only differs in length from `old` at the end of a file that grew or shrank
+    pub new: Vec<u8>,
+}
+
+/// The changes to one game file.
+#[derive(Clone)]
+pub enum FilePatch {
+    /// An RMS file, patched room by room so that unrelated edits to the same file don't get in
+    /// the way.
+    Rooms { filename: String, diff: DungeonDiff },
+    /// Anything else, patched byte by byte.
+    Bytes {
+        filename: String,
+        old_length: usize,
+        changes: Vec<ByteChange>,
+    },
+}
+
+/// Describes how a mod differs from the original game without containing the original files,
+/// so that it can be shared.
+#[derive(Clone)]
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+/// Why a patch can't be applied: the file doesn't contain what the patch expects.
+pub struct Conflict {
+    pub filename: String,
+    pub message: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.filename, self.message)
+    }
+}
+
+/// Files that belong in a patch: rooms, monsters and pictures.
+fn is_patchable(filename: &str) -> bool {
+    let filename = filename.to_ascii_uppercase();
+    filename.ends_with(".RMS") || filename.ends_with(".PIC") || filename == "PYMON.DAT"
+}
+
+fn is_rooms(filename: &str) -> bool {
+    filename.to_ascii_uppercase().ends_with(".RMS")
+}
+
+/// Every run of differing bytes. If the lengths differ, the last change covers the rest of both
+/// files.
+pub fn diff_bytes(old: &[u8], new: &[u8]) -> Vec<ByteChange> {
+    let common = old.len().min(new.len());
+    let mut changes = Vec::new();
+    let mut i = 0;
+    while i < common {
+        if old[i] == new[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < common && old[i] != new[i] {
+            i += 1;
+        }
+        changes.push(ByteChange {
+            offset: start,
+            old: old[start..i].to_vec(),
+            new: new[start..i].to_vec(),
+        });
+    }
+    if old.len() != new.len() {
+        // Join on to a change that ends right where the tail begins
+        match changes.last_mut() {
+            Some(last) if last.offset + last.old.len() == common => {
+                last.old.extend_from_slice(&old[common..]);
+                last.new.extend_from_slice(&new[common..]);
+            }
+            _ => changes.push(ByteChange {
+                offset: common,
+                old: old[common..].to_vec(),
+                new: new[common..].to_vec(),
+            }),
+        }
+    }
+    changes
+}
+
+/// Apply byte changes to `data`, which must have `old_length` bytes. Returns the conflicts
+/// instead if `data` isn't what the changes were made from.
+pub fn patch_bytes(
+    data: &[u8],
+    old_length: usize,
+    changes: &[ByteChange],
+) -> Result<Vec<u8>, Vec<String>> {
+    if data.len() != old_length {
+        return Err(vec![format!(
+            "expected {} bytes, found {}",
+            old_length,
+            data.len()
+        )]);
+    }
+    if let Err(message) = check_byte_changes(old_length, changes) {
+        return Err(vec![message]);
+    }
+    let conflicts: Vec<String> = changes
+        .iter()
+        .filter(|change| {
+            data.get(change.offset..change.offset + change.old.len()) != Some(&change.old[..])
+        })
+        .map(|change| format!("bytes at offset {:#X} don't match", change.offset))
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+    let mut patched = data.to_vec();
+    // From the end back, so that a change of length doesn't move the changes still to come
+    for change in changes.iter().rev() {
+        patched.splice(
+            change.offset..change.offset + change.old.len(),
+            change.new.iter().copied(),
+        );
+    }
+    Ok(patched)
+}
+
+/// Why byte changes can't be applied to a file of `old_length` bytes, if they can't. They have to
+/// be in order without overlapping, inside the file, and only the last one can change length,
+/// like diff_bytes() makes them.
+fn check_byte_changes(old_length: usize, changes: &[ByteChange]) -> Result<(), String> {
+    let mut end = 0;
+    for (i, change) in changes.iter().enumerate() {
+        if change.offset < end {
+            return Err(format!(
+                "the change at offset {:#X} is out of order or overlaps the one before",
+                change.offset
+            ));
+        }
+        end = match change.offset.checked_add(change.old.len()) {
+            Some(end) if end <= old_length => end,
+            _ => {
+                return Err(format!(
+                    "the change at offset {:#X} goes past the end of the file",
+                    change.offset
+                ))
+            }
+        };
+        if change.old.len() != change.new.len() && i + 1 != changes.len() {
+            return Err(format!(
+                "the change at offset {:#X} changes the length but isn't the last one",
+                change.offset
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `room` holds the old side of `change`.
+fn matches_old(change: &Change, room: &Room) -> bool {
+    match change {
+        Change::Name { old, .. } => room.name == *old,
+        Change::NameField { old, .. } => room.saved_name_field() == *old,
+        Change::Id { old, .. } => room.id() == *old,
+        Change::MonsterId { old, .. } => room.monster_id == *old,
+        Change::MonsterCount { old, .. } => room.monster_count == *old,
+        Change::Link { direction, old, .. } => room.link(*direction) == *old,
+        Change::Unknown { name, old, .. } => UNKNOWN_FIELDS
+            .iter()
+            .any(|field| field.name == *name && (field.get)(room) == *old),
+        Change::Tile { x, y, old, .. } => room.get_tile(*x, *y) == *old,
+        Change::Object { x, y, old, .. } => room.get_object(*x, *y) == *old,
+    }
+}
+
+/// Set the field to the new side of `change`. A name change has to come before a name field
+/// change, which is the order diff_rooms() produces.
+fn apply_change(change: &Change, room: &mut Room) {
+    match change {
+        Change::Name { new, .. } => room.name = new.clone(),
+        Change::NameField { new, .. } => room.set_name_field(*new),
+        Change::Id { new, .. } => room.set_id(*new),
+        Change::MonsterId { new, .. } => room.monster_id = *new,
+        Change::MonsterCount { new, .. } => room.monster_count = *new,
+        Change::Link { direction, new, .. } => room.set_link(*direction, *new),
+        Change::Unknown { name, new, .. } => {
+            for field in UNKNOWN_FIELDS.iter().filter(|field| field.name == *name) {
+                (field.set)(room, *new);
+            }
+        }
+        Change::Tile { x, y, new, .. } => room.set_tile(*x, *y, *new),
+        Change::Object { x, y, new, .. } => room.set_object(*x, *y, *new),
+    }
+}
+
+fn reverse_change(change: &Change) -> Change {
+    match change.clone() {
+        Change::Name { old, new } => Change::Name { old: new, new: old },
+        Change::NameField { old, new } => Change::NameField { old: new, new: old },
+        Change::Id { old, new } => Change::Id { old: new, new: old },
+        Change::MonsterId { old, new } => Change::MonsterId { old: new, new: old },
+        Change::MonsterCount { old, new } => Change::MonsterCount { old: new, new: old },
+        Change::Link {
+            direction,
+            old,
+            new,
+        } => Change::Link {
+            direction,
+            old: new,
+            new: old,
+        },
+        Change::Unknown { name, old, new } => Change::Unknown {
+            name,
+            old: new,
+            new: old,
+        },
+        Change::Tile { x, y, old, new } => Change::Tile {
+            x,
+            y,
+            old: new,
+            new: old,
+        },
+        Change::Object { x, y, old, new } => Change::Object {
+            x,
+            y,
+            old: new,
+            new: old,
+        },
+    }
+}
+
+/// The diff that undoes `dungeon_diff`.
+pub fn reverse_diff(dungeon_diff: &DungeonDiff) -> DungeonDiff {
+    let mut rooms: Vec<RoomDiff> = dungeon_diff
+        .rooms
+        .iter()
+        .map(|room_diff| match room_diff {
+            RoomDiff::Changed {
+                room,
+                name,
+                changes,
+            } => RoomDiff::Changed {
+                room: *room,
+                // Report the name the room will end up with
+                name: changes
+                    .iter()
+                    .find_map(|change| match change {
+                        Change::Name { old, .. } => Some(old.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| name.clone()),
+                // Name changes still need to come first
+                changes: changes.iter().map(reverse_change).collect(),
+            },
+            RoomDiff::Added { room, contents } => RoomDiff::Removed {
+                room: *room,
+                contents: contents.clone(),
+            },
+            RoomDiff::Removed { room, contents } => RoomDiff::Added {
+                room: *room,
+                contents: contents.clone(),
+            },
+        })
+        .collect();
+    rooms.sort_by_key(|room_diff| match room_diff {
+        RoomDiff::Changed { room, .. }
+        | RoomDiff::Added { room, .. }
+        | RoomDiff::Removed { room, .. } => *room,
+    });
+    DungeonDiff {
+        rooms,
+        trailer: dungeon_diff.trailer.clone().map(|(old, new)| (new, old)),
+    }
+}
+
+/// Whether the rooms follow on from each other with no gaps or repeats. They have to be sorted.
+fn is_run(rooms: &[RoomIndex]) -> bool {
+    rooms.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
+
+/// Apply a diff made by diff::diff() to a dungeon. Only the fields the diff touches have to
+/// match, so rooms the mod doesn't change can be edited freely. Returns the conflicts instead if
+/// anything doesn't match.
+pub fn patch_dungeon(
+    dungeon: &Dungeon,
+    dungeon_diff: &DungeonDiff,
+) -> Result<Dungeon, Vec<String>> {
+    let mut conflicts = Vec::new();
+    let mut patched = Dungeon {
+        rooms: dungeon.rooms.clone(),
+        trailer: dungeon.trailer.clone(),
+    };
+
+    // Rooms are only ever added to or removed from the end, one or the other, so the diff says
+    // how many rooms there should be
+    let mut added: Vec<(RoomIndex, &Room)> = Vec::new();
+    let mut removed: Vec<RoomIndex> = Vec::new();
+    for room_diff in dungeon_diff.rooms.iter() {
+        match room_diff {
+            RoomDiff::Added { room, contents } => added.push((*room, contents)),
+            RoomDiff::Removed { room, .. } => removed.push(*room),
+            RoomDiff::Changed { .. } => {}
+        }
+    }
+    added.sort_by_key(|&(room, _)| room);
+    removed.sort_unstable();
+    let added_rooms: Vec<RoomIndex> = added.iter().map(|&(room, _)| room).collect();
+    if !added.is_empty() && !removed.is_empty() {
+        conflicts.push(String::from("adds and removes rooms at the same time"));
+        return Err(conflicts);
+    }
+    if !is_run(&added_rooms) || !is_run(&removed) {
+        conflicts.push(String::from(
+            "the rooms added or removed aren't one after another",
+        ));
+        return Err(conflicts);
+    }
+    let expected_rooms = match (added_rooms.first(), removed.last()) {
+        (Some(&first), _) => Some(first),
+        (None, Some(&last)) => Some(last + 1),
+        (None, None) => None,
+    };
+    if let Some(expected_rooms) = expected_rooms {
+        if dungeon.rooms.len() != expected_rooms {
+            conflicts.push(format!(
+                "expected {} rooms, found {}",
+                expected_rooms,
+                dungeon.rooms.len()
+            ));
+            return Err(conflicts);
+        }
+    }
+
+    for room_diff in dungeon_diff.rooms.iter() {
+        match room_diff {
+            RoomDiff::Changed { room, changes, .. } => match patched.rooms.get_mut(*room) {
+                None => conflicts.push(format!("room {} doesn't exist", room + 1)),
+                Some(target) => {
+                    for change in changes.iter() {
+                        if !matches_old(change, target) {
+                            conflicts.push(format!(
+                                "room {}: can't apply \"{}\"",
+                                room + 1,
+                                change
+                            ));
+                        }
+                    }
+                    // Check every change before applying any, since a rename affects the name
+                    // field
+                    for change in changes.iter() {
+                        apply_change(change, target);
+                    }
+                }
+            },
+            RoomDiff::Removed { room, contents } => match dungeon.rooms.get(*room) {
+                None => conflicts.push(format!("room {} doesn't exist", room + 1)),
+                Some(existing) if !diff::diff_rooms(contents, existing).is_empty() => conflicts
+                    .push(format!(
+                        "room {} has been changed and can't be removed",
+                        room + 1
+                    )),
+                Some(_) => {}
+            },
+            RoomDiff::Added { .. } => {}
+        }
+    }
+    // Checked above to be the last rooms
+    patched
+        .rooms
+        .truncate(dungeon.rooms.len().saturating_sub(removed.len()));
+    patched
+        .rooms
+        .extend(added.iter().map(|&(_, contents)| contents.clone()));
+
+    if let Some((old, new)) = &dungeon_diff.trailer {
+        if dungeon.trailer != *old {
+            conflicts.push(String::from("the bytes after the last room don't match"));
+        }
+        patched.trailer = new.clone();
+    }
+    if patched.rooms.len() > rms::MAX_ROOMS {
+        conflicts.push(format!("more than {} rooms", rms::MAX_ROOMS));
+    }
+
+    if conflicts.is_empty() {
+        Ok(patched)
+    } else {
+        Err(conflicts)
+    }
+}
+
+fn read(path: &Path) -> error::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| Error::io(&path.to_string_lossy(), e))
+}
+
+fn write(path: &Path, data: &[u8]) -> error::Result<()> {
+    std::fs::write(path, data).map_err(|e| Error::io(&path.to_string_lossy(), e))
+}
+
+impl FilePatch {
+    pub fn filename(&self) -> &str {
+        match self {
+            FilePatch::Rooms { filename, .. } | FilePatch::Bytes { filename, .. } => filename,
+        }
+    }
+
+    /// The patched contents of the file, or why it can't be patched.
+    fn patch(&self, data: &[u8]) -> error::Result<Result<Vec<u8>, Vec<String>>> {
+        Ok(match self {
+            FilePatch::Rooms { filename, diff } => {
+                let dungeon = Dungeon::from_bytes(filename, data)?;
+                patch_dungeon(&dungeon, diff).map(|patched| patched.to_bytes())
+            }
+            FilePatch::Bytes {
+                old_length,
+                changes,
+                ..
+            } => patch_bytes(data, *old_length, changes),
+        })
+    }
+
+    fn reversed(&self) -> FilePatch {
+        match self {
+            FilePatch::Rooms { filename, diff } => FilePatch::Rooms {
+                filename: filename.clone(),
+                diff: reverse_diff(diff),
+            },
+            FilePatch::Bytes {
+                filename,
+                old_length,
+                changes,
+            } => FilePatch::Bytes {
+                filename: filename.clone(),
+                // Changes are checked on loading to fit inside the file, so the old bytes can
+                // always be taken away
+                old_length: changes.iter().fold(*old_length, |length, change| {
+                    length
+                        .saturating_add(change.new.len())
+                        .saturating_sub(change.old.len())
+                }),
+                changes: changes
+                    .iter()
+                    .map(|change| ByteChange {
+                        offset: change.offset,
+                        old: change.new.clone(),
+                        new: change.old.clone(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl Patch {
+    /// Compare the game files in `original_dir` against the same files in `modded_dir`. Files
+    /// that didn't change are left out.
+    pub fn create(original_dir: &Path, modded_dir: &Path) -> error::Result<Patch> {
+        let entries = std::fs::read_dir(original_dir)
+            .map_err(|e| Error::io(&original_dir.to_string_lossy(), e))?;
+        let mut filenames = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::io(&original_dir.to_string_lossy(), e))?;
+            let filename = entry.file_name().to_string_lossy().into_owned();
+            if is_patchable(&filename) {
+                filenames.push(filename);
+            }
+        }
+        filenames.sort();
+
+        let mut files = Vec::new();
+        for filename in filenames {
+            let old = read(&original_dir.join(&filename))?;
+            let new = read(&modded_dir.join(&filename))?;
+            if old == new {
+                continue;
+            }
+            files.push(if is_rooms(&filename) {
+                FilePatch::Rooms {
+                    diff: diff::diff(
+                        &Dungeon::from_bytes(&filename, &old)?,
+                        &Dungeon::from_bytes(&filename, &new)?,
+                    ),
+                    filename,
+                }
+            } else {
+                FilePatch::Bytes {
+                    filename,
+                    old_length: old.len(),
+                    changes: diff_bytes(&old, &new),
+                }
+            });
+        }
+        Ok(Patch { files })
+    }
+
+    /// The patch that undoes this one.
+    pub fn reversed(&self) -> Patch {
+        Patch {
+            files: self.files.iter().map(FilePatch::reversed).collect(),
+        }
+    }
+
+    /// Everything in the files in `dir` that doesn't match what the patch expects. Empty if the
+    /// patch can be applied.
+    pub fn check(&self, dir: &Path) -> error::Result<Vec<Conflict>> {
+        Ok(self.patched_files(dir)?.err().unwrap_or_default())
+    }
+
+    /// Patch the files in `dir`. Nothing is written unless every file can be patched, otherwise
+    /// the conflicts are returned.
+    pub fn apply(&self, dir: &Path) -> error::Result<Vec<Conflict>> {
+        match self.patched_files(dir)? {
+            Err(conflicts) => Ok(conflicts),
+            Ok(patched_files) => {
+                for (file, data) in self.files.iter().zip(patched_files) {
+                    write(&dir.join(file.filename()), &data)?;
+                }
+                Ok(vec![])
+            }
+        }
+    }
+
+    fn patched_files(&self, dir: &Path) -> error::Result<Result<Vec<Vec<u8>>, Vec<Conflict>>> {
+        let mut patched_files = Vec::new();
+        let mut conflicts = Vec::new();
+        for file in self.files.iter() {
+            match file.patch(&read(&dir.join(file.filename()))?)? {
+                Ok(data) => patched_files.push(data),
+                Err(messages) => conflicts.extend(messages.into_iter().map(|message| Conflict {
+                    filename: file.filename().to_string(),
+                    message,
+                })),
+            }
+        }
+        Ok(if conflicts.is_empty() {
+            Ok(patched_files)
+        } else {
+            Err(conflicts)
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut members = vec![(
+                    "filename".to_string(),
+                    Value::String(file.filename().to_string()),
+                )];
+                match file {
+                    FilePatch::Rooms { diff, .. } => {
+                        members.push(("kind".to_string(), Value::String("rooms".to_string())));
+                        members.extend(diff.to_json_members());
+                    }
+                    FilePatch::Bytes {
+                        old_length,
+                        changes,
+                        ..
+                    } => {
+                        members.push(("kind".to_string(), Value::String("bytes".to_string())));
+                        members.push(("old_length".to_string(), Value::Number(*old_length as f64)));
+                        let changes = changes
+                            .iter()
+                            .map(|change| {
+                                Value::Object(vec![
+                                    ("offset".to_string(), Value::Number(change.offset as f64)),
+                                    ("old".to_string(), hex(&change.old)),
+                                    ("new".to_string(), hex(&change.new)),
+                                ])
+                            })
+                            .collect();
+                        members.push(("changes".to_string(), Value::Array(changes)));
+                    }
+                }
+                Value::Object(members)
+            })
+            .collect();
+        Value::Object(vec![
+            ("format".to_string(), Value::String(FORMAT.to_string())),
+            ("version".to_string(), Value::Number(VERSION as f64)),
+            ("files".to_string(), Value::Array(files)),
+        ])
+        .to_pretty_string()
+    }
+
+    /// Parse a patch written by to_json(). `filename` is only used for errors.
+    pub fn from_json(filename: &str, text: &str) -> error::Result<Patch> {
+        let mut reader = Reader::new(filename);
+        let document = super::json::parse(text).map_err(|message| reader.error(message))?;
+        if reader.field(&document, "format")?.as_str() != Some(FORMAT) {
+            return Err(reader.error(format!("not an \"{}\" document", FORMAT)));
+        }
+        if reader.field(&document, "version")?.as_integer() != Some(VERSION) {
+            return Err(reader.error(format!("only version {} is supported", VERSION)));
+        }
+
+        let file_values = reader
+            .field(&document, "files")?
+            .as_array()
+            .ok_or_else(|| reader.error(String::from("\"files\" must be an array")))?;
+        let mut files = Vec::with_capacity(file_values.len());
+        for value in file_values.iter() {
+            reader.context = String::new();
+            let filename = reader.string_field(value, "filename")?.to_string();
+            // Patches are shared, so don't let one write outside of the game directory
+            if !is_patchable(&filename) || filename.contains(['/', '\\']) {
+                return Err(reader.error(format!("\"{}\" isn't a game file", filename)));
+            }
+            reader.context = filename.clone();
+            files.push(match reader.string_field(value, "kind")? {
+                "rooms" => FilePatch::Rooms {
+                    diff: DungeonDiff::from_json_members(&mut reader, value)?,
+                    filename,
+                },
+                "bytes" => {
+                    let change_values =
+                        reader.field(value, "changes")?.as_array().ok_or_else(|| {
+                            reader.error(String::from("\"changes\" must be an array"))
+                        })?;
+                    let changes = change_values
+                        .iter()
+                        .map(|change| {
+                            Ok(ByteChange {
+                                offset: reader.integer_field(change, "offset")?,
+                                old: reader.hex_field(change, "old")?,
+                                new: reader.hex_field(change, "new")?,
+                            })
+                        })
+                        .collect::<error::Result<Vec<ByteChange>>>()?;
+                    let old_length = reader.integer_field(value, "old_length")?;
+                    check_byte_changes(old_length, &changes)
+                        .map_err(|message| reader.error(message))?;
+                    FilePatch::Bytes {
+                        filename,
+                        old_length,
+                        changes,
+                    }
+                }
+                _ => {
+                    return Err(
+                        reader.error(String::from("\"kind\" must be \"rooms\" or \"bytes\""))
+                    )
+                }
+            });
+        }
+        Ok(Patch { files })
+    }
+}
@@ -0,0 +1,211 @@
+use super::error;
+use super::error::Error;
+use super::img;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Deflate can refer back at most this far.
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// https://www.w3.org/TR/png/#D-CRCAppendix
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xEDB88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |c, &b| {
+        table[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8)
+    })
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1950#section-8.2
+pub fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &x| {
+        let a = (a + x as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+/// Writes bits least significant first, the way deflate packs them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        for i in 0..count {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            *self.bytes.last_mut().unwrap() |= (((value >> i) & 1) as u8) << self.bit;
+            self.bit = (self.bit + 1) % 8;
+        }
+    }
+
+    /// Huffman codes are packed most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        for i in (0..length).rev() {
+            self.write((code >> i) & 1, 1);
+        }
+    }
+
+    /// A literal byte or length symbol from the fixed Huffman code.
+    /// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.6
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, length: usize) {
+        const BASES: [usize; 29] = [
+            3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99,
+            115, 131, 163, 195, 227, 258,
+        ];
+        const EXTRA_BITS: [u32; 29] = [
+            0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+        ];
+        let code = BASES.iter().rposition(|&base| base <= length).unwrap();
+        self.write_symbol(257 + code as u32);
+        self.write((length - BASES[code]) as u32, EXTRA_BITS[code]);
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        const BASES: [usize; 30] = [
+            1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025,
+            1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+        ];
+        const EXTRA_BITS: [u32; 30] = [
+            0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12,
+            12, 13, 13,
+        ];
+        let code = BASES.iter().rposition(|&base| base <= distance).unwrap();
+        // Distance codes are all 5 bits in the fixed code
+        self.write_code(code as u32, 5);
+        self.write((distance - BASES[code]) as u32, EXTRA_BITS[code]);
+    }
+}
+
+/// Compress `data` as a single fixed Huffman block, finding repeats with a hash of the next three
+/// bytes. Nowhere near as small as zlib but rooms are mostly the same few tiles so it does well
+/// enough.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        bit: 0,
+    };
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut last_seen: std::collections::HashMap<&[u8], usize> = std::collections::HashMap::new();
+    let mut i = 0;
+    while i < data.len() {
+        let key = data.get(i..i + MIN_MATCH);
+        let candidate = key
+            .and_then(|key| last_seen.get(key))
+            .copied()
+            .filter(|&start| i - start <= WINDOW_SIZE);
+        let length = candidate.map_or(0, |start| {
+            data[i..]
+                .iter()
+                .zip(data[start..].iter())
+                .take(MAX_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        let step = if length >= MIN_MATCH {
+            writer.write_length(length);
+            writer.write_distance(i - candidate.unwrap());
+            length
+        } else {
+            writer.write_symbol(data[i] as u32);
+            1
+        };
+        for position in i..i + step {
+            if let Some(key) = data.get(position..position + MIN_MATCH) {
+                last_seen.insert(key, position);
+            }
+        }
+        i += step;
+    }
+    writer.write_symbol(256);
+    writer.bytes
+}
+
+/// https://www.rfc-editor.org/rfc/rfc1950
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // 32K window, deflate, fastest compression, and a check value that makes the header a multiple
+    // of 31
+    let mut builder = vec![0x78, 0x01];
+    builder.extend(deflate(data));
+    builder.extend_from_slice(&adler32(data).to_be_bytes());
+    builder
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encode an image as an 8-bit RGBA PNG.
+///
+/// https://www.w3.org/TR/png/
+pub fn encode(image: &img::Image) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // Bit depth, truecolor with alpha, deflate, adaptive filtering, no interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    // Every scanline is filtered with "Up" so that rows repeated from the row above (most of a
+    // tile) become runs of zeros
+    let bytes: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|color| vec![color.r, color.g, color.b, color.a])
+        .collect();
+    let stride = image.width * 4;
+    let mut scanlines = Vec::with_capacity((stride + 1) * image.height);
+    for y in 0..image.height {
+        scanlines.push(2);
+        for x in 0..stride {
+            let above = if y == 0 {
+                0
+            } else {
+                bytes[(y - 1) * stride + x]
+            };
+            scanlines.push(bytes[y * stride + x].wrapping_sub(above));
+        }
+    }
+    write_chunk(&mut png, b"IDAT", &zlib(&scanlines));
+
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn save(image: &img::Image, filename: &str) -> error::Result<()> {
+    std::fs::write(filename, encode(image)).map_err(|e| Error::io(filename, e))
+}
@@ -0,0 +1,491 @@
+use std::fmt;
+
+use super::object::RoomObject;
+use super::rms::{Direction, Room, RoomIndex, Tile, TrapKind, ROOM_HEIGHT, ROOM_WIDTH};
+
+/// Why a query couldn't be parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueryError {
+    /// 1-based, in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn compare(self, left: usize, right: usize) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// Which objects `object` counts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ObjectPattern {
+    Byte(u8),
+    /// Lowercase part of the name
+    Name(String),
+}
+
+/// Something about a room that can be counted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Counted {
+    Tile(Tile),
+    /// Any kind of trap if None
+    Trap(Option<TrapKind>),
+    Object(ObjectPattern),
+    Spawns,
+    MonsterId,
+    MonsterCount,
+    Exits,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Compare(Counted, Comparison, usize),
+    Exit(Direction),
+    LinksTo(RoomIndex),
+    OneWay,
+    /// Lowercase pattern
+    Name(String),
+}
+
+/// Cells of a room, for counting.
+fn cells() -> impl Iterator<Item = (u32, u32)> {
+    (0..ROOM_HEIGHT).flat_map(|y| (0..ROOM_WIDTH).map(move |x| (x, y)))
+}
+
+/// Whether `name` matches a glob `pattern` where * is any run of characters and ? any one.
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob(rest, &name[skip..])),
+        Some((&c, rest)) => name
+            .split_first()
+            .is_some_and(|(&n, name)| (c == '?' || c == n) && glob(rest, name)),
+    }
+}
+
+impl Counted {
+    fn count(&self, room: &Room) -> usize {
+        match self {
+            Counted::Tile(tile) => cells()
+                .filter(|&(x, y)| room.get_tile(x, y) == *tile)
+                .count(),
+            Counted::Trap(kind) => cells()
+                .filter(|&(x, y)| match room.get_tile(x, y) {
+                    Tile::Trap(trap) => kind.is_none_or(|kind| kind == trap),
+                    Tile::Graphic(_) => false,
+                })
+                .count(),
+            Counted::Object(pattern) => cells()
+                .filter(|&(x, y)| {
+                    let object = room.get_object(x, y);
+                    match pattern {
+                        ObjectPattern::Byte(byte) => object.to_byte() == *byte,
+                        ObjectPattern::Name(name) => {
+                            object != RoomObject::None
+                                && object.name().to_lowercase().contains(name.as_str())
+                        }
+                    }
+                })
+                .count(),
+            Counted::Spawns => room.monster_spawns().len(),
+            Counted::MonsterId => room.monster_id as usize,
+            Counted::MonsterCount => room.monster_count as usize,
+            Counted::Exits => Direction::ALL
+                .iter()
+                .filter(|&&direction| room.link(direction).is_some())
+                .count(),
+        }
+    }
+}
+
+impl Query {
+    /// Whether the room at `index` matches. The other rooms are needed for one-way.
+    pub fn matches(&self, rooms: &[Room], index: RoomIndex) -> bool {
+        let room = &rooms[index];
+        match self {
+            Query::And(left, right) => left.matches(rooms, index) && right.matches(rooms, index),
+            Query::Or(left, right) => left.matches(rooms, index) || right.matches(rooms, index),
+            Query::Not(query) => !query.matches(rooms, index),
+            Query::Compare(counted, comparison, value) => {
+                comparison.compare(counted.count(room), *value)
+            }
+            Query::Exit(direction) => room.link(*direction).is_some(),
+            Query::LinksTo(target) => Direction::ALL
+                .iter()
+                .any(|&direction| room.link(direction) == Some(*target)),
+            Query::OneWay => Direction::ALL.iter().any(|&direction| {
+                room.link(direction).is_some_and(|target| {
+                    rooms
+                        .get(target)
+                        .is_none_or(|target| target.link(direction.opposite()) != Some(index))
+                })
+            }),
+            Query::Name(pattern) => {
+                let name = room.name.to_lowercase();
+                if pattern.contains(['*', '?']) {
+                    let pattern: Vec<char> = pattern.chars().collect();
+                    let name: Vec<char> = name.chars().collect();
+                    glob(&pattern, &name)
+                } else {
+                    name.contains(pattern.as_str())
+                }
+            }
+        }
+    }
+
+    /// Indices of every matching room.
+    pub fn filter(&self, rooms: &[Room]) -> Vec<RoomIndex> {
+        (0..rooms.len())
+            .filter(|&index| self.matches(rooms, index))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Number(usize),
+    /// "..."
+    Text(String),
+    /// 'c'
+    Character(u8),
+    Compare(Comparison),
+    Tilde,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "\"{}\"", word),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Text(text) => write!(f, "{:?}", text),
+            Token::Character(c) => write!(f, "'{}'", *c as char),
+            Token::Compare(_) => write!(f, "a comparison"),
+            Token::Tilde => write!(f, "~"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+/// Split a query into tokens, each with its 1-based column.
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let error = |message: &str| QueryError {
+            column,
+            message: message.to_string(),
+        };
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, length) = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '~' => (Token::Tilde, 1),
+            '=' if next == Some('=') => (Token::Compare(Comparison::Equal), 2),
+            '=' => (Token::Compare(Comparison::Equal), 1),
+            '!' if next == Some('=') => (Token::Compare(Comparison::NotEqual), 2),
+            '<' if next == Some('=') => (Token::Compare(Comparison::LessOrEqual), 2),
+            '<' => (Token::Compare(Comparison::Less), 1),
+            '>' if next == Some('=') => (Token::Compare(Comparison::GreaterOrEqual), 2),
+            '>' => (Token::Compare(Comparison::Greater), 1),
+            '"' => {
+                let length = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| error("the text isn't closed with \""))?;
+                let text = chars[i + 1..i + 1 + length].iter().collect();
+                (Token::Text(text), length + 2)
+            }
+            '\'' => match (next, chars.get(i + 2)) {
+                (Some(c), Some('\'')) if c.is_ascii() => (Token::Character(c as u8), 3),
+                _ => return Err(error("expected one character between 's")),
+            },
+            _ if c.is_ascii_alphanumeric() => {
+                let length = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '-' || **c == '_')
+                    .count();
+                let word: String = chars[i..i + length].iter().collect();
+                match word.parse() {
+                    Ok(number) => (Token::Number(number), length),
+                    Err(_) if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                        return Err(error("numbers can't have letters in them"))
+                    }
+                    Err(_) => (Token::Word(word.to_lowercase()), length),
+                }
+            }
+            _ => return Err(error(&format!("unexpected {:?}", c))),
+        };
+        tokens.push((column, token));
+        i += length;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Column just past the end, for errors about missing tokens
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn error(&self, message: String) -> QueryError {
+        let column = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |&(column, _)| column);
+        QueryError { column, message }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, QueryError> {
+        match self.tokens.get(self.position) {
+            Some((_, token)) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.error(format!("expected {}", expected))),
+        }
+    }
+
+    fn is_word(&self, word: &str) -> bool {
+        self.peek() == Some(&Token::Word(word.to_string()))
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.and()?;
+        while self.is_word("or") {
+            self.position += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.not()?;
+        while self.is_word("and") {
+            self.position += 1;
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        if self.is_word("not") {
+            self.position += 1;
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            let query = self.or()?;
+            if self.peek() != Some(&Token::Close) {
+                return Err(self.error(String::from("expected )")));
+            }
+            self.position += 1;
+            return Ok(query);
+        }
+        self.condition()
+    }
+
+    fn number(&mut self) -> Result<usize, QueryError> {
+        match self.next("a number")? {
+            Token::Number(number) => Ok(number),
+            token => {
+                self.position -= 1;
+                Err(self.error(format!("expected a number, not {}", token)))
+            }
+        }
+    }
+
+    /// A tile or object byte: a number or a character.
+    fn value(&mut self) -> Result<u8, QueryError> {
+        match self.next("a number or 'c'")? {
+            Token::Number(number) if number <= u8::MAX as usize => Ok(number as u8),
+            Token::Character(c) => Ok(c),
+            token => {
+                self.position -= 1;
+                Err(self.error(format!(
+                    "expected a number from 0 to 255 or 'c', not {}",
+                    token
+                )))
+            }
+        }
+    }
+
+    /// An optional comparison, defaulting to "not 0".
+    fn compare(&mut self, counted: Counted) -> Result<Query, QueryError> {
+        match self.peek() {
+            Some(&Token::Compare(comparison)) => {
+                self.position += 1;
+                Ok(Query::Compare(counted, comparison, self.number()?))
+            }
+            _ => Ok(Query::Compare(counted, Comparison::NotEqual, 0)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Query, QueryError> {
+        let word = match self.next("a condition")? {
+            Token::Word(word) => word,
+            token => {
+                self.position -= 1;
+                return Err(self.error(format!("expected a condition, not {}", token)));
+            }
+        };
+        match word.as_str() {
+            "tile" => {
+                let tile = Tile::from_byte(self.value()?);
+                self.compare(Counted::Tile(tile))
+            }
+            "trap" => {
+                let kind = match self.peek() {
+                    Some(Token::Number(_)) | Some(Token::Character(_)) => {
+                        let value = self.value()?;
+                        match TrapKind::new(value) {
+                            Some(kind) => Some(kind),
+                            None => {
+                                self.position -= 1;
+                                return Err(self.error(format!("{} isn't a trap", value)));
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+                self.compare(Counted::Trap(kind))
+            }
+            "object" => {
+                let pattern = match self.peek() {
+                    Some(Token::Text(text)) => {
+                        let name = text.to_lowercase();
+                        self.position += 1;
+                        ObjectPattern::Name(name)
+                    }
+                    _ => ObjectPattern::Byte(self.value()?),
+                };
+                self.compare(Counted::Object(pattern))
+            }
+            "spawns" => self.compare(Counted::Spawns),
+            "monster" => self.compare(Counted::MonsterId),
+            "count" => self.compare(Counted::MonsterCount),
+            "exits" => self.compare(Counted::Exits),
+            "exit" => match self.next("a direction")? {
+                Token::Word(name) => match Direction::ALL.iter().find(|d| d.name() == name) {
+                    Some(&direction) => Ok(Query::Exit(direction)),
+                    None => {
+                        self.position -= 1;
+                        Err(self.error(format!("{} isn't a direction", name)))
+                    }
+                },
+                token => {
+                    self.position -= 1;
+                    Err(self.error(format!("expected a direction, not {}", token)))
+                }
+            },
+            "links-to" => match self.number()? {
+                0 => {
+                    self.position -= 1;
+                    Err(self.error(String::from("room numbers start at 1")))
+                }
+                number => Ok(Query::LinksTo(number - 1)),
+            },
+            "one-way" => Ok(Query::OneWay),
+            "name" => {
+                if self.next("~")? != Token::Tilde {
+                    self.position -= 1;
+                    return Err(self.error(String::from("expected ~ after name")));
+                }
+                match self.next("a \"pattern\"")? {
+                    Token::Text(text) => Ok(Query::Name(text.to_lowercase())),
+                    token => {
+                        self.position -= 1;
+                        Err(self.error(format!("expected a \"pattern\", not {}", token)))
+                    }
+                }
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.error(format!("{} isn't a condition", word)))
+            }
+        }
+    }
+}
+
+/// Parse a query that picks out rooms, like `object "chest" and spawns = 0` or
+/// `name ~ "crypt*" or tile 21 > 20`.
+///
+/// ```text
+/// query      := and ("or" and)*
+/// and        := not ("and" not)*
+/// not        := "not" not | "(" query ")" | condition
+/// condition  := tile VALUE [COMPARISON]       cells with tile VALUE (a number or 'c')
+///             | trap [VALUE] [COMPARISON]     trap cells, of one kind if given
+///             | object VALUE [COMPARISON]     cells with object VALUE, or any object whose name
+///                                             contains a "string"
+///             | spawns [COMPARISON]           monster spawn points
+///             | monster [COMPARISON]          1-based monster id, 0 for none
+///             | count [COMPARISON]            how many monsters spawn
+///             | exits [COMPARISON]            how many exits lead somewhere
+///             | exit DIRECTION                has an exit north, east, south, west, up or down
+///             | links-to NUMBER               has an exit to that room
+///             | one-way                       has an exit that doesn't lead back
+///             | name ~ "pattern"              name contains the pattern, or matches it if it has
+///                                             * or ? wildcards; ignoring case
+/// COMPARISON := (= | != | < | <= | > | >=) NUMBER
+/// ```
+///
+/// Leaving out the comparison means "at least one" (or "not 0" for monster and count).
+pub fn parse(query: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        end: query.chars().count() + 1,
+    };
+    let parsed = parser.or()?;
+    match parser.peek() {
+        None => Ok(parsed),
+        Some(token) => {
+            let message = format!("unexpected {}", token);
+            Err(parser.error(message))
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use super::graph;
+use super::object::RoomObject;
+use super::random::Random;
+use super::rms::{Room, RoomIndex, Tile, ROOM_HEIGHT, ROOM_WIDTH};
+use super::validate;
+use super::walk;
+
+/// Random swaps of monsters tried per room. Each one is kept only if it leaves every floor within
+/// the tolerance.
+const MONSTER_SWAPS_PER_ROOM: usize = 4;
+
+/// What to shuffle. Room layouts, exits and anything that changes where the player can walk
+/// (doors, soft walls, movable blocks) always stay put.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    /// Give rooms each other's monsters, anywhere in the dungeon
+    pub monsters: bool,
+    /// Move spawn points around their room
+    pub spawns: bool,
+    /// Move treasure chests between the rooms on their floor
+    pub chests: bool,
+    /// Move everything else (bodies, graves, smoke, ...) around its room
+    pub objects: bool,
+    /// How far each floor's average monster may move from the original, in places in PYMON.DAT
+    pub tolerance: f64,
+    pub seed: u64,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            monsters: true,
+            spawns: true,
+            chests: true,
+            objects: true,
+            tolerance: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+/// How many things moved.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Shuffled {
+    /// Rooms with a different monster
+    pub monsters: usize,
+    pub spawns: usize,
+    pub chests: usize,
+    pub objects: usize,
+}
+
+/// The average monster on each floor as its 1-based place in PYMON.DAT, which stands in for how
+/// tough it is (see generate::Parameters::monster_curve). Floors are numbered like
+/// graph::floors_from() and floors without monsters are left out.
+pub fn floor_difficulty(rooms: &[Room]) -> BTreeMap<Option<i32>, f64> {
+    let floors = graph::floors_from(rooms, validate::STARTING_ROOM);
+    let mut totals: BTreeMap<Option<i32>, (f64, usize)> = BTreeMap::new();
+    for (room, &floor) in rooms.iter().zip(floors.iter()) {
+        if room.monster_id != 0 {
+            let total = totals.entry(floor).or_default();
+            total.0 += room.monster_id as f64;
+            total.1 += 1;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|(floor, (sum, count))| (floor, sum / count as f64))
+        .collect()
+}
+
+/// Cells an object can be moved to: walkable from the room's exits, not a trap, not on the edge
+/// where it could sit in a doorway, and not holding anything already.
+fn free_cells(room: &Room) -> Vec<(u32, u32)> {
+    let walkability = walk::walkability(room);
+    let mut cells = Vec::new();
+    for y in 1..ROOM_HEIGHT - 1 {
+        for x in 1..ROOM_WIDTH - 1 {
+            if walkability.is_reachable(x, y)
+                && !matches!(room.get_tile(x, y), Tile::Trap(_))
+                && room.get_object(x, y) == RoomObject::None
+            {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Take every object that `moves` picks out of the room, returning them.
+fn take_objects(room: &mut Room, moves: impl Fn(RoomObject) -> bool) -> Vec<RoomObject> {
+    let mut taken = Vec::new();
+    for y in 0..ROOM_HEIGHT {
+        for x in 0..ROOM_WIDTH {
+            let object = room.get_object(x, y);
+            if moves(object) {
+                taken.push(object);
+                room.set_object(x, y, RoomObject::None);
+            }
+        }
+    }
+    taken
+}
+
+/// Move every object that `moves` picks to random free cells in the room, returning how many
+/// moved. A room without enough space is left alone.
+fn scatter(room: &mut Room, moves: impl Fn(RoomObject) -> bool, random: &mut Random) -> usize {
+    let original = room.clone();
+    let objects = take_objects(room, moves);
+    let mut free = free_cells(room);
+    if free.len() < objects.len() {
+        *room = original;
+        return 0;
+    }
+    for &object in objects.iter() {
+        let (x, y) = free.swap_remove(random.below(free.len()));
+        room.set_object(x, y, object);
+    }
+    objects.len()
+}
+
+fn is_loose_object(object: RoomObject) -> bool {
+    object != RoomObject::None
+        && object != RoomObject::TreasureChest
+        && !matches!(object, RoomObject::Monster(_))
+        && walk::Passability::of_object(object).is_none()
+}
+
+/// Shuffle the dungeon according to the rules. The same rules and seed always give the same
+/// result for the same dungeon.
+pub fn randomize(rooms: &mut [Room], rules: &Rules) -> Shuffled {
+    let mut random = Random::new(rules.seed);
+    let mut shuffled = Shuffled::default();
+    let originals = rooms.to_vec();
+    let floors = graph::floors_from(rooms, validate::STARTING_ROOM);
+
+    if rules.monsters {
+        let difficulty = floor_difficulty(rooms);
+        let within_tolerance = |rooms: &[Room]| {
+            floor_difficulty(rooms)
+                .iter()
+                .all(|(floor, average)| (average - difficulty[floor]).abs() <= rules.tolerance)
+        };
+        let with_monsters: Vec<RoomIndex> = (0..rooms.len())
+            .filter(|&index| rooms[index].monster_id != 0)
+            .collect();
+        if with_monsters.len() > 1 {
+            for _ in 0..with_monsters.len() * MONSTER_SWAPS_PER_ROOM {
+                let a = with_monsters[random.below(with_monsters.len())];
+                let b = with_monsters[random.below(with_monsters.len())];
+                let (id_a, id_b) = (rooms[a].monster_id, rooms[b].monster_id);
+                rooms[a].monster_id = id_b;
+                rooms[b].monster_id = id_a;
+                if !within_tolerance(rooms) {
+                    rooms[a].monster_id = id_a;
+                    rooms[b].monster_id = id_b;
+                }
+            }
+        }
+        shuffled.monsters = rooms
+            .iter()
+            .zip(originals.iter())
+            .filter(|(room, original)| room.monster_id != original.monster_id)
+            .count();
+    }
+
+    for room in rooms.iter_mut() {
+        if rules.spawns {
+            let is_spawn = |object| matches!(object, RoomObject::Monster(_));
+            shuffled.spawns += scatter(room, is_spawn, &mut random);
+        }
+        if rules.objects {
+            shuffled.objects += scatter(room, is_loose_object, &mut random);
+        }
+    }
+
+    if rules.chests {
+        let mut by_floor: BTreeMap<Option<i32>, Vec<RoomIndex>> = BTreeMap::new();
+        for (index, &floor) in floors.iter().enumerate() {
+            by_floor.entry(floor).or_default().push(index);
+        }
+        // A floor without enough space is left alone
+        let before = rooms.to_vec();
+        for floor_rooms in by_floor.values() {
+            let mut chests = 0;
+            for &index in floor_rooms.iter() {
+                let is_chest = |object| object == RoomObject::TreasureChest;
+                chests += take_objects(&mut rooms[index], is_chest).len();
+            }
+            let mut free: Vec<(RoomIndex, (u32, u32))> = floor_rooms
+                .iter()
+                .flat_map(|&index| {
+                    free_cells(&rooms[index])
+                        .into_iter()
+                        .map(move |cell| (index, cell))
+                })
+                .collect();
+            if free.len() < chests {
+                for &index in floor_rooms.iter() {
+                    rooms[index] = before[index].clone();
+                }
+                continue;
+            }
+            shuffled.chests += chests;
+            for _ in 0..chests {
+                let (index, (x, y)) = free.swap_remove(random.below(free.len()));
+                rooms[index].set_object(x, y, RoomObject::TreasureChest);
+            }
+        }
+    }
+    shuffled
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use super::object::RoomObject;
+use super::rms;
+use super::rms::{Room, RoomIndex, Tile};
+
+/// What to look for and what to put in its place. Both sides are the same kind of thing.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Replacement {
+    Tile {
+        from: Tile,
+        to: Tile,
+    },
+    Object {
+        from: RoomObject,
+        to: RoomObject,
+    },
+    /// The room's monster setting (1-based, 0 for none) rather than a cell
+    Monster {
+        from: u8,
+        to: u8,
+    },
+}
+
+impl fmt::Display for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Replacement::Tile { from, to } => write!(f, "{} -> {}", from, to),
+            Replacement::Object { from, to } => write!(f, "{} -> {}", from, to),
+            Replacement::Monster { from, to } => write!(f, "monster {} -> {}", from, to),
+        }
+    }
+}
+
+/// A rectangle of cells, inclusive on all sides.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Region {
+    /// Every cell of a room.
+    pub const ALL: Region = Region {
+        left: 0,
+        top: 0,
+        right: rms::ROOM_WIDTH - 1,
+        bottom: rms::ROOM_HEIGHT - 1,
+    };
+
+    fn cells(self) -> impl Iterator<Item = (u32, u32)> {
+        let right = self.right.min(rms::ROOM_WIDTH - 1);
+        let bottom = self.bottom.min(rms::ROOM_HEIGHT - 1);
+        (self.top..=bottom).flat_map(move |y| (self.left..=right).map(move |x| (x, y)))
+    }
+}
+
+/// Where to replace. The region only limits tiles and objects, since the monster setting belongs
+/// to the whole room.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scope {
+    /// Every room if None
+    pub rooms: Option<Vec<RoomIndex>>,
+    pub region: Region,
+}
+
+impl Scope {
+    /// Every cell of every room.
+    pub fn everywhere() -> Scope {
+        Scope {
+            rooms: None,
+            region: Region::ALL,
+        }
+    }
+
+    fn includes(&self, room: RoomIndex) -> bool {
+        self.rooms
+            .as_ref()
+            .is_none_or(|rooms| rooms.contains(&room))
+    }
+}
+
+/// How many cells (or monster settings) would change in one room.
+fn count(room: &Room, replacement: Replacement, region: Region) -> usize {
+    match replacement {
+        Replacement::Tile { from, to } if from != to => region
+            .cells()
+            .filter(|&(x, y)| room.get_tile(x, y) == from)
+            .count(),
+        Replacement::Object { from, to } if from != to => region
+            .cells()
+            .filter(|&(x, y)| room.get_object(x, y) == from)
+            .count(),
+        Replacement::Monster { from, to } if from != to => (room.monster_id == from) as usize,
+        _ => 0,
+    }
+}
+
+/// The rooms that would change and how many cells in each, without changing anything.
+pub fn preview(rooms: &[Room], replacement: Replacement, scope: &Scope) -> Vec<(RoomIndex, usize)> {
+    rooms
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| scope.includes(index))
+        .map(|(index, room)| (index, count(room, replacement, scope.region)))
+        .filter(|&(_, count)| count > 0)
+        .collect()
+}
+
+/// Make the replacement, returning the same counts as preview().
+pub fn replace(
+    rooms: &mut [Room],
+    replacement: Replacement,
+    scope: &Scope,
+) -> Vec<(RoomIndex, usize)> {
+    let changes = preview(rooms, replacement, scope);
+    for &(index, _) in changes.iter() {
+        let room = &mut rooms[index];
+        match replacement {
+            Replacement::Tile { from, to } => {
+                for (x, y) in scope.region.cells() {
+                    if room.get_tile(x, y) == from {
+                        room.set_tile(x, y, to);
+                    }
+                }
+            }
+            Replacement::Object { from, to } => {
+                for (x, y) in scope.region.cells() {
+                    if room.get_object(x, y) == from {
+                        room.set_object(x, y, to);
+                    }
+                }
+            }
+            Replacement::Monster { to, .. } => room.monster_id = to,
+        }
+    }
+    changes
+}
+
+/// One line per room, then the total.
+pub fn summarize(rooms: &[Room], changes: &[(RoomIndex, usize)]) -> String {
+    let mut summary = String::new();
+    for &(index, count) in changes.iter() {
+        summary.push_str(&format!(
+            "room {} \"{}\": {}\n",
+            index + 1,
+            rooms[index].name,
+            count
+        ));
+    }
+    let total: usize = changes.iter().map(|&(_, count)| count).sum();
+    summary.push_str(&format!("{} in {} rooms\n", total, changes.len()));
+    summary
+}
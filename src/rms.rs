@@ -1,5 +1,8 @@
-use std::io::Write;
+use std::fmt;
 
+use super::error;
+use super::error::Error;
+use super::object::RoomObject;
 use super::pascal;
 
 pub const ROOM_WIDTH: u32 = 20;
@@ -7,165 +10,501 @@ pub const ROOM_HEIGHT: u32 = 8;
 
 const ROOM_AREA: usize = (ROOM_WIDTH * ROOM_HEIGHT) as usize;
 
-const ROOM_RECORD_SIZE: usize = 0x168;
-const ROOM_RECORD_UNKNOWN_A_OFFSET: usize = 0x0;
+pub const ROOM_RECORD_SIZE: usize = 0x168;
+pub const ROOM_RECORD_UNKNOWN_A_OFFSET: usize = 0x0;
 const ROOM_RECORD_TILE_OFFSET: usize = 0x1;
 const ROOM_RECORD_OBJECT_OFFSET: usize = 0xA1;
 const ROOM_RECORD_MONSTER_ID_OFFSET: usize = 0x141;
 const ROOM_RECORD_MONSTER_COUNT_OFFSET: usize = 0x142;
-const ROOM_RECORD_NORTH_OFFSET: usize = 0x143;
+const ROOM_RECORD_NAV_OFFSET: usize = 0x143;
 const ROOM_RECORD_ID_OFFSET: usize = 0x149;
-const ROOM_RECORD_UNKNOWN_B_OFFSET: usize = 0x14A;
-const ROOM_RECORD_UNKNOWN_C_OFFSET: usize = 0x14B;
-const ROOM_RECORD_UNKNOWN_D_OFFSET: usize = 0x14C;
+pub const ROOM_RECORD_UNKNOWN_B_OFFSET: usize = 0x14A;
+pub const ROOM_RECORD_UNKNOWN_C_OFFSET: usize = 0x14B;
+pub const ROOM_RECORD_UNKNOWN_D_OFFSET: usize = 0x14C;
 const ROOM_RECORD_NAME_OFFSET: usize = 0x14D;
 
-const ROOM_RECORD_NAME_MAX_LENGTH: u8 = (ROOM_RECORD_SIZE - ROOM_RECORD_NAME_OFFSET) as u8;
+pub const ROOM_RECORD_NAME_SIZE: usize = ROOM_RECORD_SIZE - ROOM_RECORD_NAME_OFFSET;
+/// In characters, since the name field also holds the length prefix
+pub const ROOM_RECORD_NAME_MAX_LENGTH: u8 = (ROOM_RECORD_NAME_SIZE - 1) as u8;
 
-pub enum ObjectType {
-    None,
-    Monster,
-    Object,
+/// 0-based index into the room list. The RMS format itself stores 1-based room numbers with 0
+/// meaning "nowhere", which is what the nav accessors translate to and from.
+pub type RoomIndex = usize;
+
+/// Ways out of a room, in the order they are stored in the record.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    /// The direction that leads back, e.g. a north exit should be answered by a south exit.
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::East => "east",
+            Direction::South => "south",
+            Direction::West => "west",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+/// Tile values from here up are traps, where each kind of trap is a different ASCII character.
+const TRAP_FIRST_CHARACTER: u8 = b'U';
+/// The last printable character, used to cycle through trap kinds.
+const TRAP_LAST_CHARACTER: u8 = b'~';
+/// Traps look like ordinary floor, so this is what gets drawn under the trap marker.
+pub const TRAP_BASE_TILE: u8 = 21;
+
+/// Which trap a cell holds, as the character stored in the tile layer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct TrapKind(u8);
+
+impl TrapKind {
+    /// None if `character` is an ordinary tile.
+    pub fn new(character: u8) -> Option<TrapKind> {
+        if character >= TRAP_FIRST_CHARACTER {
+            Some(TrapKind(character))
+        } else {
+            None
+        }
+    }
+
+    pub fn character(self) -> u8 {
+        self.0
+    }
+
+    /// The next printable trap character, wrapping around. Used to pick a kind while painting.
+    pub fn next(self) -> TrapKind {
+        if self.0 >= TRAP_LAST_CHARACTER {
+            TrapKind(TRAP_FIRST_CHARACTER)
+        } else {
+            TrapKind(self.0 + 1)
+        }
+    }
+}
+
+impl Default for TrapKind {
+    fn default() -> TrapKind {
+        TrapKind(TRAP_FIRST_CHARACTER)
+    }
+}
+
+/// What a cell of the tile layer holds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Tile {
+    /// 1-based EGAPICS tile. 0 is the null tile, which isn't drawn.
+    Graphic(u8),
+    Trap(TrapKind),
+}
+
+impl Tile {
+    pub fn from_byte(byte: u8) -> Tile {
+        match TrapKind::new(byte) {
+            Some(kind) => Tile::Trap(kind),
+            None => Tile::Graphic(byte),
+        }
+    }
+
+    /// The byte to store in the record. Traps keep the exact character they were loaded with.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Tile::Graphic(tile) => tile,
+            Tile::Trap(kind) => kind.character(),
+        }
+    }
+
+    /// The 1-based EGAPICS tile to draw for this cell, 0 for nothing.
+    pub fn graphic(self) -> u8 {
+        match self {
+            Tile::Graphic(tile) => tile,
+            Tile::Trap(_) => TRAP_BASE_TILE,
+        }
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tile::Graphic(tile) => write!(f, "tile {}", tile),
+            Tile::Trap(kind) => write!(f, "trap '{}'", kind.character() as char),
+        }
+    }
 }
 
 /// TODO: Figure out and document unknowns
+#[derive(Clone)]
 pub struct Room {
-    unknown_a: u8,
+    pub unknown_a: u8,
     tiles: [u8; ROOM_AREA],
     objects: [u8; ROOM_AREA],
+    /// 1-based index into PYMON.DAT, 0 for none
     pub monster_id: u8,
-    monster_count: u8,
-    // TODO: Make nav an array with enums?
-    pub nav_north: u8,
-    pub nav_east: u8,
-    pub nav_south: u8,
-    pub nav_west: u8,
-    pub nav_up: u8,
-    pub nav_down: u8,
+    /// How many monsters the room spawns
+    pub monster_count: u8,
+    /// 1-based room numbers indexed by Direction, 0 means no exit. Use link()/set_link().
+    nav: [u8; Direction::ALL.len()],
     id: u8,
-    unknown_b: u8,
-    unknown_c: u8,
-    unknown_d: u8,
+    pub unknown_b: u8,
+    pub unknown_c: u8,
+    pub unknown_d: u8,
     pub name: String,
+    /// The name field as it was loaded, including whatever follows the string. EXPLORER.EXE
+    /// doesn't clear the field between uses, so there's often leftover text from older names.
+    /// Kept around so that saving doesn't disturb it.
+    name_field: [u8; ROOM_RECORD_NAME_SIZE],
+}
+
+/// Everything in an RMS file.
+pub struct Dungeon {
+    pub rooms: Vec<Room>,
+    /// Bytes after the last complete record. Not a room, but written back untouched.
+    pub trailer: Vec<u8>,
 }
 
 impl Room {
-    /// The null tile is 0 and should not be drawn, so don't forget to -1 the return value.
-    pub fn get_tile(&self, x: u32, y: u32) -> u8 {
+    /// An empty room with no exits, monsters or objects.
+    pub fn new(name: &str) -> Room {
+        Room {
+            unknown_a: 0,
+            tiles: [0; ROOM_AREA],
+            objects: [0; ROOM_AREA],
+            monster_id: 0,
+            monster_count: 0,
+            nav: [0; Direction::ALL.len()],
+            id: 0,
+            unknown_b: 0,
+            unknown_c: 0,
+            unknown_d: 0,
+            name: name.to_string(),
+            name_field: [0; ROOM_RECORD_NAME_SIZE],
+        }
+    }
+
+    pub fn get_tile(&self, x: u32, y: u32) -> Tile {
         // TODO: Panic if x or y out of bounds
-        let tile = self.tiles[(y * ROOM_WIDTH + x) as usize];
-        // Different traps are different ASCII characters, which is what > 84 catches
-        return if tile > 84 { 21 } else { tile };
+        Tile::from_byte(self.tiles[(y * ROOM_WIDTH + x) as usize])
     }
 
-    pub fn set_tile(&mut self, x: u32, y: u32, tile: u8) {
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile) {
         // TODO: Panic if x or y out of bounds
-        self.tiles[(y * ROOM_WIDTH + x) as usize] = tile;
+        self.tiles[(y * ROOM_WIDTH + x) as usize] = tile.to_byte();
     }
 
-    pub fn get_object_type(&self, x: u32, y: u32) -> ObjectType {
+    pub fn get_object(&self, x: u32, y: u32) -> RoomObject {
         // TODO: Panic if x or y out of bounds
-        let tile = self.objects[(y * ROOM_WIDTH + x) as usize];
-        if tile == 0 {
-            return ObjectType::None;
+        RoomObject::from_byte(self.objects[(y * ROOM_WIDTH + x) as usize])
+    }
+
+    pub fn set_object(&mut self, x: u32, y: u32, object: RoomObject) {
+        // TODO: Panic if x or y out of bounds
+        self.objects[(y * ROOM_WIDTH + x) as usize] = object.to_byte();
+    }
+
+    /// The 0-based index into the monster list of the room's monster, if it has one.
+    pub fn monster_index(&self) -> Option<usize> {
+        (self.monster_id as usize).checked_sub(1)
+    }
+
+    /// Every cell in the object layer where a monster appears.
+    pub fn monster_spawns(&self) -> Vec<(u32, u32)> {
+        (0..ROOM_HEIGHT)
+            .flat_map(|y| (0..ROOM_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(self.get_object(x, y), RoomObject::Monster(_)))
+            .collect()
+    }
+
+    /// The room's own 1-based room number, which should match its position in the file.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: u8) {
+        self.id = id;
+    }
+
+    /// The name field as it was loaded, including any leftovers after the name itself.
+    pub fn name_field(&self) -> &[u8; ROOM_RECORD_NAME_SIZE] {
+        &self.name_field
+    }
+
+    /// Replace what's kept underneath the name. `name` still decides what the field starts with.
+    pub fn set_name_field(&mut self, name_field: [u8; ROOM_RECORD_NAME_SIZE]) {
+        self.name_field = name_field;
+    }
+
+    /// The name field as it will be saved: the current name written over whatever was left in the
+    /// field.
+    ///
+    /// A name that doesn't fit is saved as much of it as does; see check_name().
+    pub fn saved_name_field(&self) -> [u8; ROOM_RECORD_NAME_SIZE] {
+        let name_pstr = pascal::to_pascal_string_lossy(&self.name, ROOM_RECORD_NAME_MAX_LENGTH);
+        let mut name_field = self.name_field;
+        name_field[..name_pstr.len()].copy_from_slice(&name_pstr);
+        name_field
+    }
+
+    /// Whether the name can be saved without losing anything: it has to fit in the name field and
+    /// only use characters from the DOS character set.
+    pub fn check_name(&self) -> Result<(), pascal::PascalError> {
+        pascal::to_pascal_string(&self.name, ROOM_RECORD_NAME_MAX_LENGTH).map(|_| ())
+    }
+
+    /// Where the exit in `direction` leads, if anywhere.
+    pub fn link(&self, direction: Direction) -> Option<RoomIndex> {
+        match self.nav[direction as usize] {
+            0 => None,
+            room_number => Some(room_number as RoomIndex - 1),
         }
-        if tile <= 'c' as u8 {
-            return ObjectType::Monster;
+    }
+
+    /// Change a single exit without touching the room on the other side. Prefer link_rooms() or
+    /// unlink_rooms() which keep both sides consistent.
+    ///
+    /// Panics if `target` can't be stored as a 1-based u8.
+    pub fn set_link(&mut self, direction: Direction, target: Option<RoomIndex>) {
+        self.nav[direction as usize] = match target {
+            None => 0,
+            Some(index) => {
+                assert!(index < u8::MAX as RoomIndex, "room index out of range");
+                index as u8 + 1
+            }
+        };
+    }
+}
+
+/// Remove the exit in `direction` from room `from`, and the exit leading back from the room on the
+/// other side if it points at `from`.
+pub fn unlink_rooms(rooms: &mut [Room], from: RoomIndex, direction: Direction) {
+    if let Some(to) = rooms[from].link(direction) {
+        if let Some(back) = rooms.get_mut(to) {
+            if back.link(direction.opposite()) == Some(from) {
+                back.set_link(direction.opposite(), None);
+            }
         }
-        return ObjectType::Object;
     }
+    rooms[from].set_link(direction, None);
+}
 
-    pub fn get_object(&self, x: u32, y: u32) -> u8 {
-        // TODO: Panic if x or y out of bounds
-        let tile = self.objects[(y * ROOM_WIDTH + x) as usize];
-        return get_object_tile(tile as char);
-    }
-}
-
-fn get_object_tile(object: char) -> u8 {
-    match object {
-        'd' => return 47, // Magical darkness
-        'e' => return 21, // Treasure chest
-        'f' => return 46, // Smoke
-        'g' => return 29, // Movable block
-        'h' => return 37, // Door (vertical)
-        'i' => return 36, // Door (horizontal)
-        'j' => return 0,  // TODO: Funny looking chest
-        'k' => return 0,  // TODO: Soft section of wall
-        'l' => return 42, // Soft piece of wall
-        'm' => return 18, // Soft pile of rubble
-        'n' => return 22, // Old body
-        'o' => return 17, // Old bones
-        'p' => return 49, // Old stone coffin
-        'q' => return 54, // Old grave
-        'r' => return 0,  // TODO: Movable glass block
-        's' => return 0,  // TODO: Old skeleton
-        't' => return 0,  // TODO: Old skeleton
-        'u' => return 0,  // TODO: Hollow obilisk
-        'v' => return 82, // "Just some blood"
-        'w' => return 0,  // TODO: Stone marker
-        _ => return 0,
-    }
-}
-
-pub fn load_rooms(filename: &str) -> Vec<Room> {
-    let rms_data = std::fs::read(filename).unwrap();
-    return rms_data
-        .chunks(ROOM_RECORD_SIZE)
-        .map(|x| {
-            let mut room = Room {
-                unknown_a: x[ROOM_RECORD_UNKNOWN_A_OFFSET],
-                tiles: [0; ROOM_AREA],
-                objects: [0; ROOM_AREA],
-                monster_id: x[ROOM_RECORD_MONSTER_ID_OFFSET],
-                monster_count: x[ROOM_RECORD_MONSTER_COUNT_OFFSET],
-                nav_north: x[ROOM_RECORD_NORTH_OFFSET],
-                nav_east: x[ROOM_RECORD_NORTH_OFFSET + 1],
-                nav_south: x[ROOM_RECORD_NORTH_OFFSET + 2],
-                nav_west: x[ROOM_RECORD_NORTH_OFFSET + 3],
-                nav_up: x[ROOM_RECORD_NORTH_OFFSET + 4],
-                nav_down: x[ROOM_RECORD_NORTH_OFFSET + 5],
-                id: x[ROOM_RECORD_ID_OFFSET],
-                unknown_b: x[ROOM_RECORD_UNKNOWN_B_OFFSET],
-                unknown_c: x[ROOM_RECORD_UNKNOWN_C_OFFSET],
-                unknown_d: x[ROOM_RECORD_UNKNOWN_D_OFFSET],
-                name: pascal::from_pascal_string(&x[ROOM_RECORD_NAME_OFFSET..ROOM_RECORD_SIZE]),
-            };
-            room.tiles
-                .copy_from_slice(&x[ROOM_RECORD_TILE_OFFSET..ROOM_RECORD_TILE_OFFSET + ROOM_AREA]);
-            room.objects.copy_from_slice(
-                &x[ROOM_RECORD_OBJECT_OFFSET..ROOM_RECORD_OBJECT_OFFSET + ROOM_AREA],
-            );
-            room
+/// Link room `from` to room `to` in `direction`, and `to` back to `from` in the opposite
+/// direction. Any exits these replace are unlinked on both sides first so that no one-way links
+/// are left behind.
+///
+/// Panics if either index is out of bounds.
+pub fn link_rooms(rooms: &mut [Room], from: RoomIndex, direction: Direction, to: RoomIndex) {
+    assert!(to < rooms.len(), "room index out of range");
+    unlink_rooms(rooms, from, direction);
+    unlink_rooms(rooms, to, direction.opposite());
+    rooms[from].set_link(direction, Some(to));
+    rooms[to].set_link(direction.opposite(), Some(from));
+}
+
+/// Room numbers are stored in a u8 and 0 is reserved for "no room".
+pub const MAX_ROOMS: usize = u8::MAX as usize;
+
+/// Fix up every link and id after rooms have been moved around. `new_index[old]` is where the
+/// room that used to be at `old` ended up, or None if it's gone. Links to removed rooms are
+/// cleared. Links that were already broken (past the end of the list) are left alone.
+fn renumber(rooms: &mut [Room], new_index: &[Option<RoomIndex>]) {
+    let old_count = new_index.len();
+    for room in rooms.iter_mut() {
+        for &direction in Direction::ALL.iter() {
+            if let Some(target) = room.link(direction).filter(|&target| target < old_count) {
+                room.set_link(direction, new_index[target]);
+            }
+        }
+        let id = room.id as RoomIndex;
+        if id > 0 && id <= old_count {
+            room.id = new_index[id - 1].map_or(0, |index| index as u8 + 1);
+        }
+    }
+}
+
+/// Add `room` to the end of the dungeon, giving it the matching id. Returns its index, or None
+/// if the dungeon is full.
+pub fn append_room(rooms: &mut Vec<Room>, mut room: Room) -> Option<RoomIndex> {
+    if rooms.len() >= MAX_ROOMS {
+        return None;
+    }
+    room.id = rooms.len() as u8 + 1;
+    rooms.push(room);
+    Some(rooms.len() - 1)
+}
+
+/// Append a copy of the room at `index`. The copy has no exits since nothing leads back to it.
+/// Returns the copy's index, or None if the dungeon is full.
+pub fn duplicate_room(rooms: &mut Vec<Room>, index: RoomIndex) -> Option<RoomIndex> {
+    let mut copy = rooms[index].clone();
+    copy.nav = [0; Direction::ALL.len()];
+    append_room(rooms, copy)
+}
+
+/// Overwrite the room at `index` with a copy of `room`, which can come from another dungeon. The
+/// room keeps its id, and its own exits unless `with_links`. Copied exits that lead past the last
+/// room are dropped and nothing is linked back, so check the result for one-way links.
+pub fn paste_room(rooms: &mut [Room], index: RoomIndex, room: &Room, with_links: bool) {
+    let mut copy = room.clone();
+    copy.id = rooms[index].id;
+    if with_links {
+        for &direction in Direction::ALL.iter() {
+            if copy
+                .link(direction)
+                .is_some_and(|target| target >= rooms.len())
+            {
+                copy.set_link(direction, None);
+            }
+        }
+    } else {
+        copy.nav = rooms[index].nav;
+    }
+    rooms[index] = copy;
+}
+
+/// Remove the room at `index`. Links to it are removed and every later room moves up by one.
+pub fn delete_room(rooms: &mut Vec<Room>, index: RoomIndex) {
+    let new_index: Vec<Option<RoomIndex>> = (0..rooms.len())
+        .map(|old| match old {
+            old if old < index => Some(old),
+            old if old == index => None,
+            old => Some(old - 1),
         })
         .collect();
+    rooms.remove(index);
+    renumber(rooms, &new_index);
+}
+
+/// Move the room at `from` so that it ends up at `to`, shifting the rooms in between.
+pub fn move_room(rooms: &mut Vec<Room>, from: RoomIndex, to: RoomIndex) {
+    assert!(to < rooms.len(), "room index out of range");
+    // Work out the new order of the old indices, then invert it
+    let mut order: Vec<RoomIndex> = (0..rooms.len()).collect();
+    order.remove(from);
+    order.insert(to, from);
+    let mut new_index = vec![None; rooms.len()];
+    for (new, &old) in order.iter().enumerate() {
+        new_index[old] = Some(new);
+    }
+
+    let room = rooms.remove(from);
+    rooms.insert(to, room);
+    renumber(rooms, &new_index);
+}
+
+impl Room {
+    /// Parse one ROOM_RECORD_SIZE record. `filename` and `record` are only used for errors.
+    fn from_record(filename: &str, record: usize, x: &[u8]) -> error::Result<Room> {
+        let name = pascal::from_pascal_string(&x[ROOM_RECORD_NAME_OFFSET..ROOM_RECORD_SIZE])
+            .ok_or_else(|| Error::InvalidString {
+                filename: filename.to_string(),
+                record,
+                offset: record * ROOM_RECORD_SIZE + ROOM_RECORD_NAME_OFFSET,
+            })?;
+        let mut room = Room {
+            unknown_a: x[ROOM_RECORD_UNKNOWN_A_OFFSET],
+            tiles: [0; ROOM_AREA],
+            objects: [0; ROOM_AREA],
+            monster_id: x[ROOM_RECORD_MONSTER_ID_OFFSET],
+            monster_count: x[ROOM_RECORD_MONSTER_COUNT_OFFSET],
+            nav: [0; Direction::ALL.len()],
+            id: x[ROOM_RECORD_ID_OFFSET],
+            unknown_b: x[ROOM_RECORD_UNKNOWN_B_OFFSET],
+            unknown_c: x[ROOM_RECORD_UNKNOWN_C_OFFSET],
+            unknown_d: x[ROOM_RECORD_UNKNOWN_D_OFFSET],
+            name,
+            name_field: [0; ROOM_RECORD_NAME_SIZE],
+        };
+        room.tiles
+            .copy_from_slice(&x[ROOM_RECORD_TILE_OFFSET..ROOM_RECORD_TILE_OFFSET + ROOM_AREA]);
+        room.objects
+            .copy_from_slice(&x[ROOM_RECORD_OBJECT_OFFSET..ROOM_RECORD_OBJECT_OFFSET + ROOM_AREA]);
+        room.nav.copy_from_slice(
+            &x[ROOM_RECORD_NAV_OFFSET..ROOM_RECORD_NAV_OFFSET + Direction::ALL.len()],
+        );
+        room.name_field
+            .copy_from_slice(&x[ROOM_RECORD_NAME_OFFSET..ROOM_RECORD_SIZE]);
+        Ok(room)
+    }
+
+    /// Serialize to a ROOM_RECORD_SIZE record. An unmodified room produces exactly the bytes it
+    /// was loaded from.
+    fn to_record(&self) -> Vec<u8> {
+        // TODO: Is there a more Rust-idiomatic way of data serialization?
+        let mut record = Vec::with_capacity(ROOM_RECORD_SIZE);
+        record.push(self.unknown_a);
+        record.extend_from_slice(&self.tiles);
+        record.extend_from_slice(&self.objects);
+        record.push(self.monster_id);
+        record.push(self.monster_count);
+        record.extend_from_slice(&self.nav);
+        record.push(self.id);
+        record.push(self.unknown_b);
+        record.push(self.unknown_c);
+        record.push(self.unknown_d);
+        // Only overwrite as much of the name field as the current name needs. Anything after
+        // that is whatever was there when we loaded it.
+        record.extend_from_slice(&self.saved_name_field());
+        record
+    }
+}
+
+impl Dungeon {
+    /// Parse the contents of an RMS file. `filename` is only used for errors.
+    pub fn from_bytes(filename: &str, rms_data: &[u8]) -> error::Result<Dungeon> {
+        let complete_length = rms_data.len() - rms_data.len() % ROOM_RECORD_SIZE;
+        let rooms = rms_data[..complete_length]
+            .chunks(ROOM_RECORD_SIZE)
+            .enumerate()
+            .map(|(i, x)| Room::from_record(filename, i, x))
+            .collect::<error::Result<Vec<Room>>>()?;
+        Ok(Dungeon {
+            rooms,
+            trailer: rms_data[complete_length..].to_vec(),
+        })
+    }
+
+    /// Serialize to the contents of an RMS file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut rms_data = Vec::with_capacity(self.rooms.len() * ROOM_RECORD_SIZE);
+        for room in &self.rooms {
+            rms_data.extend_from_slice(&room.to_record());
+        }
+        rms_data.extend_from_slice(&self.trailer);
+        rms_data
+    }
+}
+
+pub fn load_rooms(filename: &str) -> error::Result<Dungeon> {
+    let rms_data = std::fs::read(filename).map_err(|e| Error::io(filename, e))?;
+    Dungeon::from_bytes(filename, &rms_data)
 }
 
-pub fn save_rooms(rooms: &Vec<Room>, filename: &str) -> std::io::Result<()> {
-    // TODO: Is there a more Rust-idiomatic way of data serialization?
-    let mut file = std::fs::File::create(filename)?;
-    for room in rooms {
-        file.write(&vec![room.unknown_a])?;
-        file.write(&room.tiles)?;
-        file.write(&room.objects)?;
-        file.write(&vec![room.monster_id])?;
-        file.write(&vec![room.monster_count])?;
-        file.write(&vec![room.nav_north])?;
-        file.write(&vec![room.nav_east])?;
-        file.write(&vec![room.nav_south])?;
-        file.write(&vec![room.nav_west])?;
-        file.write(&vec![room.nav_up])?;
-        file.write(&vec![room.nav_down])?;
-        file.write(&vec![room.id])?;
-        file.write(&vec![room.unknown_b])?;
-        file.write(&vec![room.unknown_c])?;
-        file.write(&vec![room.unknown_d])?;
-        let mut name_pstr = pascal::to_pascal_string(&room.name, ROOM_RECORD_NAME_MAX_LENGTH);
-        // Strings are up to ROOM_RECORD_NAME_MAX_LENGTH long, 0 padded otherwise
-        // TODO: This is an unproven assumption. Look at how EXPLORER.EXE loads the record.
-        // Make sure we're not trampling important data.
-        name_pstr.resize(ROOM_RECORD_NAME_MAX_LENGTH as usize, 0);
-        file.write(&name_pstr)?;
-    }
-    Ok(())
+pub fn save_rooms(dungeon: &Dungeon, filename: &str) -> error::Result<()> {
+    std::fs::write(filename, dungeon.to_bytes()).map_err(|e| Error::io(filename, e))
 }
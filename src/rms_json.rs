@@ -0,0 +1,269 @@
+use super::error;
+use super::error::Error;
+use super::json::Value;
+use super::object::RoomObject;
+use super::rms;
+use super::rms::{Direction, Dungeon, Room, Tile};
+
+/// Identifies our JSON documents, in case other tools grow their own.
+const FORMAT: &str = "rmsedit dungeon";
+const VERSION: i64 = 1;
+
+/// Bytes as pairs of lower case hex digits, the way hex_field() reads them back.
+pub(crate) fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> Value {
+    Value::String(hex_string(bytes))
+}
+
+fn number(n: u8) -> Value {
+    Value::Number(n as f64)
+}
+
+/// One array per row of the room, `get` picks the layer.
+fn grid(get: impl Fn(u32, u32) -> u8) -> Value {
+    Value::Array(
+        (0..rms::ROOM_HEIGHT)
+            .map(|y| Value::Array((0..rms::ROOM_WIDTH).map(|x| number(get(x, y))).collect()))
+            .collect(),
+    )
+}
+
+/// Every field of the room, as it appears in the "rooms" array.
+pub fn room_to_json(room: &Room) -> Value {
+    let links = Direction::ALL
+        .iter()
+        .map(|&direction| {
+            // 1-based like the game, 0 for no exit
+            let room_number = room.link(direction).map_or(0, |index| index + 1);
+            (
+                direction.name().to_string(),
+                Value::Number(room_number as f64),
+            )
+        })
+        .collect();
+    Value::Object(vec![
+        ("name".to_string(), Value::String(room.name.clone())),
+        ("name_field".to_string(), hex(room.name_field())),
+        ("id".to_string(), number(room.id())),
+        ("unknown_a".to_string(), number(room.unknown_a)),
+        ("unknown_b".to_string(), number(room.unknown_b)),
+        ("unknown_c".to_string(), number(room.unknown_c)),
+        ("unknown_d".to_string(), number(room.unknown_d)),
+        ("monster_id".to_string(), number(room.monster_id)),
+        ("monster_count".to_string(), number(room.monster_count)),
+        ("links".to_string(), Value::Object(links)),
+        (
+            "tiles".to_string(),
+            grid(|x, y| room.get_tile(x, y).to_byte()),
+        ),
+        (
+            "objects".to_string(),
+            grid(|x, y| room.get_object(x, y).to_byte()),
+        ),
+    ])
+}
+
+/// Describe every byte of the dungeon as a JSON document. from_json() turns it back into exactly
+/// the same dungeon.
+pub fn to_json(dungeon: &Dungeon) -> String {
+    Value::Object(vec![
+        ("format".to_string(), Value::String(FORMAT.to_string())),
+        ("version".to_string(), Value::Number(VERSION as f64)),
+        (
+            "rooms".to_string(),
+            Value::Array(dungeon.rooms.iter().map(room_to_json).collect()),
+        ),
+        ("trailer".to_string(), hex(&dungeon.trailer)),
+    ])
+    .to_pretty_string()
+}
+
+/// Turns JSON values into record fields, remembering where we are for error messages. Also used
+/// by other documents that embed rooms.
+pub struct Reader<'a> {
+    filename: &'a str,
+    /// e.g. "room 3"
+    pub context: String,
+}
+
+impl<'a> Reader<'a> {
+    /// `filename` is only used for errors.
+    pub fn new(filename: &'a str) -> Reader<'a> {
+        Reader {
+            filename,
+            context: String::new(),
+        }
+    }
+
+    pub fn error(&self, message: String) -> Error {
+        Error::Format {
+            filename: self.filename.to_string(),
+            message: if self.context.is_empty() {
+                message
+            } else {
+                format!("{}: {}", self.context, message)
+            },
+        }
+    }
+
+    pub fn field<'v>(&self, value: &'v Value, key: &str) -> error::Result<&'v Value> {
+        value
+            .get(key)
+            .ok_or_else(|| self.error(format!("missing \"{}\"", key)))
+    }
+
+    pub fn byte(&self, value: &Value, what: &str) -> error::Result<u8> {
+        match value.as_integer() {
+            Some(n) if n >= 0 && n <= u8::MAX as i64 => Ok(n as u8),
+            _ => Err(self.error(format!("{} must be a whole number from 0 to 255", what))),
+        }
+    }
+
+    pub fn byte_field(&self, value: &Value, key: &str) -> error::Result<u8> {
+        self.byte(self.field(value, key)?, &format!("\"{}\"", key))
+    }
+
+    pub fn integer_field(&self, value: &Value, key: &str) -> error::Result<usize> {
+        match self.field(value, key)?.as_integer() {
+            Some(n) if n >= 0 => Ok(n as usize),
+            _ => Err(self.error(format!("\"{}\" must be a whole number", key))),
+        }
+    }
+
+    pub fn string_field<'v>(&self, value: &'v Value, key: &str) -> error::Result<&'v str> {
+        self.field(value, key)?
+            .as_str()
+            .ok_or_else(|| self.error(format!("\"{}\" must be a string", key)))
+    }
+
+    pub fn hex_field(&self, value: &Value, key: &str) -> error::Result<Vec<u8>> {
+        let text = self
+            .field(value, key)?
+            .as_str()
+            .ok_or_else(|| self.error(format!("\"{}\" must be a string", key)))?;
+        if text.len() % 2 != 0 || !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error(format!("\"{}\" must be pairs of hex digits", key)));
+        }
+        Ok((0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect())
+    }
+
+    /// Read a ROOM_HEIGHT x ROOM_WIDTH array of arrays, calling `set` for every cell.
+    fn grid_field(
+        &self,
+        value: &Value,
+        key: &str,
+        mut set: impl FnMut(u32, u32, u8),
+    ) -> error::Result<()> {
+        let rows = self
+            .field(value, key)?
+            .as_array()
+            .filter(|rows| rows.len() == rms::ROOM_HEIGHT as usize)
+            .ok_or_else(|| {
+                self.error(format!("\"{}\" must have {} rows", key, rms::ROOM_HEIGHT))
+            })?;
+        for (y, row) in rows.iter().enumerate() {
+            let cells = row
+                .as_array()
+                .filter(|cells| cells.len() == rms::ROOM_WIDTH as usize)
+                .ok_or_else(|| {
+                    self.error(format!(
+                        "\"{}\" row {} must have {} cells",
+                        key,
+                        y + 1,
+                        rms::ROOM_WIDTH
+                    ))
+                })?;
+            for (x, cell) in cells.iter().enumerate() {
+                let what = format!("\"{}\" row {} cell {}", key, y + 1, x + 1);
+                set(x as u32, y as u32, self.byte(cell, &what)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn room(&self, value: &Value) -> error::Result<Room> {
+        let name = self
+            .field(value, "name")?
+            .as_str()
+            .ok_or_else(|| self.error(String::from("\"name\" must be a string")))?;
+        let mut room = Room::new(name);
+        room.check_name()
+            .map_err(|e| self.error(format!("name: {}", e)))?;
+
+        let name_field = self.hex_field(value, "name_field")?;
+        if name_field.len() != rms::ROOM_RECORD_NAME_SIZE {
+            return Err(self.error(format!(
+                "\"name_field\" must be {} bytes",
+                rms::ROOM_RECORD_NAME_SIZE
+            )));
+        }
+        let mut field = [0; rms::ROOM_RECORD_NAME_SIZE];
+        field.copy_from_slice(&name_field);
+        room.set_name_field(field);
+
+        room.set_id(self.byte_field(value, "id")?);
+        room.unknown_a = self.byte_field(value, "unknown_a")?;
+        room.unknown_b = self.byte_field(value, "unknown_b")?;
+        room.unknown_c = self.byte_field(value, "unknown_c")?;
+        room.unknown_d = self.byte_field(value, "unknown_d")?;
+        room.monster_id = self.byte_field(value, "monster_id")?;
+        room.monster_count = self.byte_field(value, "monster_count")?;
+
+        let links = self.field(value, "links")?;
+        for &direction in Direction::ALL.iter() {
+            let room_number = self.byte_field(links, direction.name())?;
+            room.set_link(direction, (room_number as usize).checked_sub(1));
+        }
+
+        self.grid_field(value, "tiles", |x, y, byte| {
+            room.set_tile(x, y, Tile::from_byte(byte))
+        })?;
+        self.grid_field(value, "objects", |x, y, byte| {
+            room.set_object(x, y, RoomObject::from_byte(byte))
+        })?;
+        Ok(room)
+    }
+}
+
+/// Parse a document written by to_json(). Anything that wouldn't fit in an RMS file is refused.
+/// `filename` is only used for errors.
+pub fn from_json(filename: &str, text: &str) -> error::Result<Dungeon> {
+    let mut reader = Reader::new(filename);
+    let document = super::json::parse(text).map_err(|message| reader.error(message))?;
+
+    if reader.field(&document, "format")?.as_str() != Some(FORMAT) {
+        return Err(reader.error(format!("not an \"{}\" document", FORMAT)));
+    }
+    if reader.field(&document, "version")?.as_integer() != Some(VERSION) {
+        return Err(reader.error(format!("only version {} is supported", VERSION)));
+    }
+
+    let room_values = reader
+        .field(&document, "rooms")?
+        .as_array()
+        .ok_or_else(|| reader.error(String::from("\"rooms\" must be an array")))?;
+    if room_values.len() > rms::MAX_ROOMS {
+        return Err(reader.error(format!("more than {} rooms", rms::MAX_ROOMS)));
+    }
+    let trailer = reader.hex_field(&document, "trailer")?;
+    // Any longer and it would be read back as a room
+    if trailer.len() >= rms::ROOM_RECORD_SIZE {
+        return Err(reader.error(format!(
+            "\"trailer\" must be shorter than {} bytes",
+            rms::ROOM_RECORD_SIZE
+        )));
+    }
+
+    let mut rooms = Vec::with_capacity(room_values.len());
+    for (i, value) in room_values.iter().enumerate() {
+        reader.context = format!("room {}", i + 1);
+        rooms.push(reader.room(value)?);
+    }
+    Ok(Dungeon { rooms, trailer })
+}
@@ -0,0 +1,417 @@
+use std::fmt::Write;
+
+use super::error;
+use super::error::Error;
+use super::object::RoomObject;
+use super::rms;
+use super::rms::{Direction, Dungeon, Room, Tile};
+use super::rms_json::hex_string;
+
+/// First line of every file, so that we don't try to compile something else.
+const HEADER: &str = "rmsedit dungeon 1";
+/// First line of a file holding a single room.
+const ROOM_HEADER: &str = "rmsedit room 1";
+
+/// Empty cells are drawn with this.
+const EMPTY_CELL: char = '.';
+/// Lines starting with this are ignored, except inside a grid.
+const COMMENT: char = '#';
+/// How many entries of the key to write per line.
+const KEY_ENTRIES_PER_LINE: usize = 12;
+
+/// Bytes that aren't printable ASCII are drawn as the Latin Extended character this far above the
+/// byte, so every byte gets its own character without clashing with the ASCII ones.
+const NON_ASCII_CELL_BASE: u32 = 0x100;
+
+/// The character for a byte in either layer. The game already stores traps and objects as ASCII
+/// letters so those are drawn as themselves.
+fn cell_char(byte: u8) -> char {
+    match byte {
+        0 => EMPTY_CELL,
+        b'!'..=b'~' if byte as char != EMPTY_CELL => byte as char,
+        _ => std::char::from_u32(NON_ASCII_CELL_BASE + byte as u32).unwrap(),
+    }
+}
+
+fn cell_byte(c: char) -> Option<u8> {
+    match c {
+        EMPTY_CELL => Some(0),
+        '!'..='~' => Some(c as u8),
+        _ if c as u32 >= NON_ASCII_CELL_BASE && (c as u32) < NON_ASCII_CELL_BASE + 0x100 => {
+            Some((c as u32 - NON_ASCII_CELL_BASE) as u8)
+        }
+        _ => None,
+    }
+}
+
+/// A comment listing which byte each of the Latin Extended characters in the grids stands for.
+/// Many of them look alike (ā, ă, ą, ...) so it's easy to type the wrong one when editing by hand.
+fn write_key(out: &mut String, rooms: &[Room]) {
+    let mut bytes: Vec<u8> = rooms
+        .iter()
+        .flat_map(|room| {
+            (0..rms::ROOM_HEIGHT).flat_map(move |y| {
+                (0..rms::ROOM_WIDTH).flat_map(move |x| {
+                    vec![
+                        room.get_tile(x, y).to_byte(),
+                        room.get_object(x, y).to_byte(),
+                    ]
+                })
+            })
+        })
+        .filter(|&byte| cell_char(byte) as u32 >= NON_ASCII_CELL_BASE)
+        .collect();
+    if bytes.is_empty() {
+        return;
+    }
+    bytes.sort_unstable();
+    bytes.dedup();
+    writeln!(
+        out,
+        "{} Cells that aren't printable ASCII are the character {:#X} above their byte:",
+        COMMENT, NON_ASCII_CELL_BASE
+    )
+    .unwrap();
+    for line in bytes.chunks(KEY_ENTRIES_PER_LINE) {
+        let entries: Vec<String> = line
+            .iter()
+            .map(|&byte| format!("{}={}", cell_char(byte), byte))
+            .collect();
+        writeln!(out, "{} {}", COMMENT, entries.join(" ")).unwrap();
+    }
+}
+
+fn write_grid(out: &mut String, get: impl Fn(u32, u32) -> u8) {
+    for y in 0..rms::ROOM_HEIGHT {
+        let row: String = (0..rms::ROOM_WIDTH).map(|x| cell_char(get(x, y))).collect();
+        out.push_str(&row);
+        out.push('\n');
+    }
+}
+
+/// Room numbers are 1-based like the game, "-" for no exit.
+fn write_link(out: &mut String, room: &Room, direction: Direction) {
+    match room.link(direction) {
+        Some(index) => writeln!(out, "{}: {}", direction.name(), index + 1).unwrap(),
+        None => writeln!(out, "{}: -", direction.name()).unwrap(),
+    }
+}
+
+/// Names are quoted so leading and trailing spaces survive editors. Control characters are
+/// written as \xNN so that a name can't break the line.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_ascii_control() => write!(quoted, "\\x{:02x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    let byte = u8::from_str_radix(&hex, 16).ok().filter(u8::is_ascii)?;
+                    unquoted.push(byte as char);
+                }
+                c => unquoted.push(c),
+            },
+            '"' => return None,
+            c => unquoted.push(c),
+        }
+    }
+    Some(unquoted)
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => {
+                u8::from_str_radix(&format!("{}{}", *high as char, *low as char), 16).ok()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Write a single room, starting with its "room" line.
+pub fn write_room(out: &mut String, number: usize, room: &Room) {
+    writeln!(out, "room {}", number).unwrap();
+    out.push_str("tiles:\n");
+    write_grid(out, |x, y| room.get_tile(x, y).to_byte());
+    out.push_str("objects:\n");
+    write_grid(out, |x, y| room.get_object(x, y).to_byte());
+    writeln!(out, "name: {}", quote(&room.name)).unwrap();
+    // Leftovers from older names are noise to a reader, so only mention them if there are any
+    let name_field = room.saved_name_field();
+    if name_field != Room::new(&room.name).saved_name_field() {
+        writeln!(out, "name_field: {}", hex_string(&name_field)).unwrap();
+    }
+    writeln!(out, "monster: {}", room.monster_id).unwrap();
+    writeln!(out, "count: {}", room.monster_count).unwrap();
+    for &direction in Direction::ALL.iter() {
+        write_link(out, room, direction);
+    }
+    writeln!(out, "id: {}", room.id()).unwrap();
+    writeln!(out, "unknown_a: {}", room.unknown_a).unwrap();
+    writeln!(out, "unknown_b: {}", room.unknown_b).unwrap();
+    writeln!(out, "unknown_c: {}", room.unknown_c).unwrap();
+    writeln!(out, "unknown_d: {}", room.unknown_d).unwrap();
+}
+
+/// Write the whole dungeon in a form that reads well in diffs. from_text() turns it back into
+/// exactly the same dungeon.
+pub fn to_text(dungeon: &Dungeon) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", HEADER).unwrap();
+    write_key(&mut out, &dungeon.rooms);
+    for (i, room) in dungeon.rooms.iter().enumerate() {
+        out.push('\n');
+        write_room(&mut out, i + 1, room);
+    }
+    if !dungeon.trailer.is_empty() {
+        writeln!(out, "\ntrailer: {}", hex_string(&dungeon.trailer)).unwrap();
+    }
+    out
+}
+
+/// Write one room on its own, for sharing or the clipboard. It keeps its room number and exits so
+/// that they can be pasted back into the same dungeon.
+pub fn room_to_text(number: usize, room: &Room) -> String {
+    let mut out = String::new();
+    writeln!(out, "{}", ROOM_HEADER).unwrap();
+    write_key(&mut out, std::slice::from_ref(room));
+    out.push('\n');
+    write_room(&mut out, number, room);
+    out
+}
+
+/// Walks through the lines of a file, remembering where it is for error messages.
+pub struct Lines<'a> {
+    filename: &'a str,
+    lines: Vec<&'a str>,
+    /// 0-based index of the next line
+    position: usize,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(filename: &'a str, text: &'a str) -> Lines<'a> {
+        Lines {
+            filename,
+            lines: text.lines().collect(),
+            position: 0,
+        }
+    }
+
+    /// An error about the line that was just read.
+    pub fn error(&self, message: String) -> Error {
+        Error::Format {
+            filename: self.filename.to_string(),
+            message: format!("line {}: {}", self.position.max(1), message),
+        }
+    }
+
+    /// Skip blank lines and comments. Grid rows are read with next_line() so they can't be
+    /// mistaken for comments.
+    fn skip_blank(&mut self) {
+        while self.lines.get(self.position).is_some_and(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with(COMMENT)
+        }) {
+            self.position += 1;
+        }
+    }
+
+    /// The next non-blank line, if there is one, without consuming it.
+    pub fn peek(&mut self) -> Option<&'a str> {
+        self.skip_blank();
+        self.lines.get(self.position).copied()
+    }
+
+    fn next_line(&mut self) -> error::Result<&'a str> {
+        let line = self.lines.get(self.position).copied();
+        self.position += 1;
+        line.ok_or_else(|| self.error(String::from("unexpected end of file")))
+    }
+
+    /// Read a "key: value" line, which must have the given key.
+    fn value(&mut self, key: &str) -> error::Result<&'a str> {
+        self.skip_blank();
+        let line = self.next_line()?;
+        match line.split_once(':') {
+            Some((k, value)) if k.trim() == key => Ok(value.trim()),
+            _ => Err(self.error(format!("expected \"{}:\"", key))),
+        }
+    }
+
+    fn byte(&mut self, key: &str) -> error::Result<u8> {
+        let value = self.value(key)?;
+        value
+            .parse()
+            .map_err(|_| self.error(format!("{} must be a number from 0 to 255", key)))
+    }
+
+    fn grid(&mut self, key: &str, mut set: impl FnMut(u32, u32, u8)) -> error::Result<()> {
+        if !self.value(key)?.is_empty() {
+            return Err(self.error(format!("the {} start on the next line", key)));
+        }
+        for y in 0..rms::ROOM_HEIGHT {
+            let row: Vec<char> = self.next_line()?.trim_end().chars().collect();
+            if row.len() != rms::ROOM_WIDTH as usize {
+                return Err(self.error(format!(
+                    "{} rows must be {} characters wide",
+                    key,
+                    rms::ROOM_WIDTH
+                )));
+            }
+            for (x, &c) in row.iter().enumerate() {
+                let byte = cell_byte(c)
+                    .ok_or_else(|| self.error(format!("'{}' isn't a valid cell", c)))?;
+                set(x as u32, y, byte);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a room written by write_room(), checking that it's numbered `number`.
+    pub fn room(&mut self, number: usize) -> error::Result<Room> {
+        self.skip_blank();
+        let line = self.next_line()?;
+        if line.trim() != format!("room {}", number) {
+            return Err(self.error(format!("expected \"room {}\"", number)));
+        }
+
+        let mut tiles = [[0u8; rms::ROOM_WIDTH as usize]; rms::ROOM_HEIGHT as usize];
+        let mut objects = [[0u8; rms::ROOM_WIDTH as usize]; rms::ROOM_HEIGHT as usize];
+        self.grid("tiles", |x, y, byte| tiles[y as usize][x as usize] = byte)?;
+        self.grid("objects", |x, y, byte| {
+            objects[y as usize][x as usize] = byte
+        })?;
+
+        let name = self.value("name")?;
+        let name = unquote(name)
+            .ok_or_else(|| self.error(String::from("the name must be in double quotes")))?;
+        let mut room = Room::new(&name);
+        room.check_name()
+            .map_err(|e| self.error(format!("name: {}", e)))?;
+        if self
+            .peek()
+            .is_some_and(|line| line.starts_with("name_field:"))
+        {
+            let field = unhex(self.value("name_field")?)
+                .filter(|field| field.len() == rms::ROOM_RECORD_NAME_SIZE)
+                .ok_or_else(|| {
+                    self.error(format!(
+                        "name_field must be {} bytes of hex",
+                        rms::ROOM_RECORD_NAME_SIZE
+                    ))
+                })?;
+            let mut name_field = [0; rms::ROOM_RECORD_NAME_SIZE];
+            name_field.copy_from_slice(&field);
+            room.set_name_field(name_field);
+        }
+
+        for y in 0..rms::ROOM_HEIGHT {
+            for x in 0..rms::ROOM_WIDTH {
+                room.set_tile(x, y, Tile::from_byte(tiles[y as usize][x as usize]));
+                room.set_object(x, y, RoomObject::from_byte(objects[y as usize][x as usize]));
+            }
+        }
+
+        room.monster_id = self.byte("monster")?;
+        room.monster_count = self.byte("count")?;
+        for &direction in Direction::ALL.iter() {
+            let value = self.value(direction.name())?;
+            let target = match value {
+                "-" => None,
+                _ => match value.parse::<u8>() {
+                    Ok(room_number) if room_number > 0 => Some(room_number as usize - 1),
+                    _ => {
+                        return Err(self.error(format!(
+                            "{} must be a room number from 1 to 255 or -",
+                            direction.name()
+                        )))
+                    }
+                },
+            };
+            room.set_link(direction, target);
+        }
+        room.set_id(self.byte("id")?);
+        room.unknown_a = self.byte("unknown_a")?;
+        room.unknown_b = self.byte("unknown_b")?;
+        room.unknown_c = self.byte("unknown_c")?;
+        room.unknown_d = self.byte("unknown_d")?;
+        Ok(room)
+    }
+}
+
+/// Compile a file written by to_text(). Anything that wouldn't fit in an RMS file is refused.
+/// `filename` is only used for errors.
+pub fn from_text(filename: &str, text: &str) -> error::Result<Dungeon> {
+    let mut lines = Lines::new(filename, text);
+    if lines.peek().map(str::trim) != Some(HEADER) {
+        return Err(lines.error(format!("expected \"{}\"", HEADER)));
+    }
+    lines.position += 1;
+
+    let mut rooms = Vec::new();
+    let mut trailer = Vec::new();
+    while let Some(line) = lines.peek() {
+        if line.starts_with("trailer:") {
+            trailer = unhex(lines.value("trailer")?)
+                .filter(|trailer| trailer.len() < rms::ROOM_RECORD_SIZE)
+                .ok_or_else(|| {
+                    lines.error(format!(
+                        "trailer must be less than {} bytes of hex",
+                        rms::ROOM_RECORD_SIZE
+                    ))
+                })?;
+            if lines.peek().is_some() {
+                return Err(lines.error(String::from("the trailer must come last")));
+            }
+            break;
+        }
+        if rooms.len() >= rms::MAX_ROOMS {
+            return Err(lines.error(format!("more than {} rooms", rms::MAX_ROOMS)));
+        }
+        rooms.push(lines.room(rooms.len() + 1)?);
+    }
+    Ok(Dungeon { rooms, trailer })
+}
+
+/// Read a room written by room_to_text(). `filename` is only used for errors.
+pub fn room_from_text(filename: &str, text: &str) -> error::Result<Room> {
+    let mut lines = Lines::new(filename, text);
+    if lines.peek().map(str::trim) != Some(ROOM_HEADER) {
+        return Err(lines.error(format!("expected \"{}\"", ROOM_HEADER)));
+    }
+    lines.position += 1;
+
+    let number = lines
+        .peek()
+        .and_then(|line| line.trim().strip_prefix("room "))
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(|| lines.error(String::from("expected \"room\" and its number")))?;
+    let room = lines.room(number)?;
+    if lines.peek().is_some() {
+        return Err(lines.error(String::from("a room file holds only one room")));
+    }
+    Ok(room)
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use super::img;
+use super::monster::Monster;
+use super::rms;
+use super::sprites;
+
+/// Space between thumbnails and around the edge of the sheet.
+const PADDING: usize = 8;
+/// Room for one line of text under each thumbnail.
+const LABEL_HEIGHT: usize = 20;
+
+const THUMBNAIL_WIDTH: usize = rms::ROOM_WIDTH as usize * img::IMAGE_DIMENSION_USIZE;
+const THUMBNAIL_HEIGHT: usize = rms::ROOM_HEIGHT as usize * img::IMAGE_DIMENSION_USIZE;
+
+const BACKGROUND: img::Color = img::Color::rgb(0x00, 0x00, 0x00);
+const LABEL_COLOR: img::Color = img::Color::rgb(0xFF, 0xFF, 0xFF);
+
+/// Draw `source` over `destination` with its top left corner at (x, y), blending by alpha.
+/// Anything wider than `max_width` or past the edge of `destination` is cut off.
+fn blit(destination: &mut img::Image, source: &img::Image, x: usize, y: usize, max_width: usize) {
+    let width = source
+        .width
+        .min(max_width)
+        .min(destination.width.saturating_sub(x));
+    let height = source.height.min(destination.height.saturating_sub(y));
+    for source_y in 0..height {
+        for source_x in 0..width {
+            let over = source.pixels[source_y * source.width + source_x];
+            let under = &mut destination.pixels[(y + source_y) * destination.width + x + source_x];
+            let alpha = over.a as u32;
+            let mix = |over: u8, under: u8| {
+                ((over as u32 * alpha + under as u32 * (255 - alpha)) / 255) as u8
+            };
+            *under = img::Color {
+                r: mix(over.r, under.r),
+                g: mix(over.g, under.g),
+                b: mix(over.b, under.b),
+                a: under.a,
+            };
+        }
+    }
+}
+
+/// Draw every room into one image, `columns` rooms across, with "number: name" under each.
+/// Rooms are drawn the same way the editor draws them. `render_text` turns a string into an image
+/// with a transparent background, which is used for the labels and for markers.
+pub fn contact_sheet(
+    rooms: &[rms::Room],
+    tiles: &[img::Image],
+    monster_sprites: &[img::Image],
+    monsters: &[Monster],
+    columns: usize,
+    render_text: &mut dyn FnMut(&str, img::Color) -> img::Image,
+) -> img::Image {
+    let columns = columns.max(1).min(rooms.len().max(1));
+    let rows = rooms.len().div_ceil(columns);
+    let cell_width = THUMBNAIL_WIDTH + PADDING;
+    let cell_height = THUMBNAIL_HEIGHT + LABEL_HEIGHT + PADDING;
+    let mut sheet = img::Image {
+        width: PADDING + columns * cell_width,
+        height: PADDING + rows * cell_height,
+        pixels: Vec::new(),
+    };
+    sheet.pixels = vec![BACKGROUND; sheet.width * sheet.height];
+
+    // Markers are the same handful of characters over and over
+    let mut markers: HashMap<(char, (u8, u8, u8)), img::Image> = HashMap::new();
+    for (index, room) in rooms.iter().enumerate() {
+        let left = PADDING + index % columns * cell_width;
+        let top = PADDING + index / columns * cell_height;
+        for y in 0..rms::ROOM_HEIGHT {
+            for x in 0..rms::ROOM_WIDTH {
+                let cell_left = left + x as usize * img::IMAGE_DIMENSION_USIZE;
+                let cell_top = top + y as usize * img::IMAGE_DIMENSION_USIZE;
+                let sprites =
+                    sprites::cell_sprites(room, monsters, tiles.len(), monster_sprites.len(), x, y);
+                for sprite in sprites {
+                    match sprite {
+                        sprites::Sprite::Tile(tile) => {
+                            blit(&mut sheet, &tiles[tile], cell_left, cell_top, usize::MAX)
+                        }
+                        sprites::Sprite::Monster(monster) => blit(
+                            &mut sheet,
+                            &monster_sprites[monster],
+                            cell_left,
+                            cell_top,
+                            usize::MAX,
+                        ),
+                        sprites::Sprite::Marker(character, color) => {
+                            let marker = markers
+                                .entry((character, (color.r, color.g, color.b)))
+                                .or_insert_with(|| render_text(&character.to_string(), color));
+                            // Centered on the cell, like the editor
+                            let center_x = cell_left + img::IMAGE_DIMENSION_USIZE / 2;
+                            let center_y = cell_top + img::IMAGE_DIMENSION_USIZE / 2;
+                            blit(
+                                &mut sheet,
+                                marker,
+                                center_x.saturating_sub(marker.width / 2),
+                                center_y.saturating_sub(marker.height / 2),
+                                usize::MAX,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let label = render_text(&format!("{}: {}", index + 1, room.name), LABEL_COLOR);
+        blit(
+            &mut sheet,
+            &label,
+            left,
+            top + THUMBNAIL_HEIGHT,
+            THUMBNAIL_WIDTH,
+        );
+    }
+    sheet
+}
@@ -0,0 +1,135 @@
+use super::error;
+use super::img;
+use super::monster::Monster;
+use super::object::RoomObject;
+use super::rms;
+use super::rms::Tile;
+
+/// Only a handful of EGAPICS/CGAPICS tiles have masks: (tile, mask), both 1-based.
+const TILE_MASKS: [(usize, usize); 13] = [
+    (10, 64), // Attack effect
+    (11, 69), // Hit explosion
+    (18, 71), // Old bones
+    (22, 65), // Treasure chest
+    (23, 70), // Old body
+    (24, 68), // Player
+    (47, 67), // Smoke
+    (50, 72), // Old stone coffin
+    (55, 66), // Old grave
+    (60, 82), // ???
+    (75, 73), // ???
+    (76, 74), // ???
+    (83, 84), // Some old blood
+];
+
+/// Traps are marked with their character in this color.
+pub const TRAP_MARKER_COLOR: img::Color = img::Color::rgb(0xFF, 0x55, 0x55);
+/// Objects we don't have a sprite for are marked with their letter in this color.
+pub const OBJECT_MARKER_COLOR: img::Color = img::Color::rgb(0x55, 0xFF, 0xFF);
+
+/// Make every pixel that's white in the mask transparent.
+pub fn apply_mask(image: &mut img::Image, mask_image: &img::Image) {
+    image
+        .pixels
+        .iter_mut()
+        .zip(mask_image.pixels.iter())
+        .for_each(|(pixel, mask)| {
+            if *mask == img::Color::rgb(0xFF, 0xFF, 0xFF) {
+                pixel.a = 0;
+            }
+        });
+}
+
+/// Load EGAPICS/CGAPICS with the masks applied. Graphic N (1-based) is at index N - 1.
+pub fn load_tiles(filename: &str) -> error::Result<Vec<img::Image>> {
+    // EGAPICS/CGAPICS contains both color data and masks. In order to use apply_mask
+    // (inside Rust's borrow rules) I load a second immutable copy. Alternatively, I could
+    // just implement Copy on Image... Or I could return a copy from apply_mask and not take
+    // a mutable reference
+    let mut tiles_color = img::load_spritesheet(filename)?;
+    let tiles_mask = img::load_spritesheet(filename)?;
+    for (tile, mask) in TILE_MASKS.iter() {
+        if let (Some(color), Some(mask)) = (tiles_color.get_mut(tile - 1), tiles_mask.get(mask - 1))
+        {
+            apply_mask(color, mask);
+        }
+    }
+    Ok(tiles_color)
+}
+
+/// Load PYMON.PIC with PYMASK.PIC applied.
+pub fn load_monsters(color_filename: &str, mask_filename: &str) -> error::Result<Vec<img::Image>> {
+    let mut monster_color = img::load_spritesheet(color_filename)?;
+    let monster_mask = img::load_spritesheet(mask_filename)?;
+    monster_color
+        .iter_mut()
+        .zip(monster_mask.iter())
+        .for_each(|(color, mask)| apply_mask(color, mask));
+    Ok(monster_color)
+}
+
+/// The index into the monster sprites of the room's monster, or None if the room's monster doesn't
+/// exist.
+pub fn monster_sprite(room: &rms::Room, monsters: &[Monster]) -> Option<usize> {
+    let monster = monsters.get(room.monster_index()?)?;
+    (monster.gfx_id as usize).checked_sub(1)
+}
+
+/// One thing to draw in a cell of a room.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Sprite {
+    /// 0-based index into the tiles
+    Tile(usize),
+    /// 0-based index into the monster sprites
+    Monster(usize),
+    /// A character to draw where we don't have a sprite
+    Marker(char, img::Color),
+}
+
+/// Everything drawn in cell (x, y) of `room`, bottom layer first. `tile_count` and
+/// `monster_sprite_count` are how many sprites were loaded, so that anything past the end is
+/// marked instead.
+pub fn cell_sprites(
+    room: &rms::Room,
+    monsters: &[Monster],
+    tile_count: usize,
+    monster_sprite_count: usize,
+    x: u32,
+    y: u32,
+) -> Vec<Sprite> {
+    let mut sprites = Vec::new();
+
+    let tile = room.get_tile(x, y);
+    if let Some(graphic) = (tile.graphic() as usize).checked_sub(1) {
+        if graphic < tile_count {
+            sprites.push(Sprite::Tile(graphic));
+        }
+    }
+    if let Tile::Trap(kind) = tile {
+        // Mark each kind of trap with its character
+        sprites.push(Sprite::Marker(kind.character() as char, TRAP_MARKER_COLOR));
+    }
+
+    match room.get_object(x, y) {
+        RoomObject::None => {}
+        RoomObject::Monster(_) => sprites.push(
+            match monster_sprite(room, monsters).filter(|&sprite| sprite < monster_sprite_count) {
+                Some(sprite) => Sprite::Monster(sprite),
+                // validate_monsters() explains what's wrong
+                None => Sprite::Marker('?', TRAP_MARKER_COLOR),
+            },
+        ),
+        object => sprites.push(
+            match object
+                .sprite()
+                .and_then(|sprite| (sprite as usize).checked_sub(1))
+                .filter(|&sprite| sprite < tile_count)
+            {
+                Some(sprite) => Sprite::Tile(sprite),
+                // Show the object's letter until we know what it looks like
+                None => Sprite::Marker(object.to_byte() as char, OBJECT_MARKER_COLOR),
+            },
+        ),
+    }
+    sprites
+}
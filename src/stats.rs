@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+
+use super::graph;
+use super::object::RoomObject;
+use super::rms::{Direction, Room, RoomIndex, Tile, TrapKind, ROOM_HEIGHT, ROOM_WIDTH};
+
+/// How one monster from PYMON.DAT is used.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MonsterUsage {
+    /// Rooms that have it as their monster
+    pub rooms: usize,
+    /// Spawn points in those rooms
+    pub spawns: usize,
+    /// Sum of the rooms' monster counts
+    pub count: usize,
+}
+
+/// Totals for the rooms on one floor.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FloorStatistics {
+    pub rooms: usize,
+    pub treasure_chests: usize,
+    pub spawns: usize,
+    pub traps: usize,
+}
+
+/// Numbers for judging how a dungeon is paced and how generous it is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    pub rooms: usize,
+    /// Cells of each tile, traps excluded
+    pub tiles: BTreeMap<u8, usize>,
+    pub traps: BTreeMap<TrapKind, usize>,
+    /// Cells of each object by byte, spawn points excluded
+    pub objects: BTreeMap<u8, usize>,
+    /// By 1-based monster id, rooms without a monster excluded
+    pub monsters: BTreeMap<u8, MonsterUsage>,
+    /// Spawn points in every room
+    pub spawns: usize,
+    /// By floor, counting down ladders from the starting room. None is the rooms that can't be
+    /// reached.
+    pub floors: BTreeMap<Option<i32>, FloorStatistics>,
+    /// Rooms that can't be left
+    pub rooms_without_exits: Vec<RoomIndex>,
+    /// Exits in every room, so a two-way link counts twice
+    pub links: usize,
+}
+
+impl Statistics {
+    pub fn average_links(&self) -> f64 {
+        if self.rooms == 0 {
+            0.0
+        } else {
+            self.links as f64 / self.rooms as f64
+        }
+    }
+
+    /// Every statistic as `statistic,key,value` rows, for spreadsheets.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("statistic,key,value\n");
+        let mut row = |statistic: &str, key: &str, value: &dyn fmt::Display| {
+            writeln!(csv, "{},{},{}", statistic, csv_field(key), value).unwrap()
+        };
+        row("rooms", "", &self.rooms);
+        row("links", "", &self.links);
+        row(
+            "average links per room",
+            "",
+            &format!("{:.2}", self.average_links()),
+        );
+        row("rooms without exits", "", &self.rooms_without_exits.len());
+        row("spawn points", "", &self.spawns);
+        for (&tile, count) in self.tiles.iter() {
+            row("tile", &tile.to_string(), count);
+        }
+        for (kind, count) in self.traps.iter() {
+            row("trap", &(kind.character() as char).to_string(), count);
+        }
+        for (&object, count) in self.objects.iter() {
+            row("object", RoomObject::from_byte(object).name(), count);
+        }
+        for (&id, usage) in self.monsters.iter() {
+            row("monster rooms", &id.to_string(), &usage.rooms);
+            row("monster spawn points", &id.to_string(), &usage.spawns);
+            row("monster count", &id.to_string(), &usage.count);
+        }
+        for (&floor, floor_statistics) in self.floors.iter() {
+            let key = floor_name(floor);
+            row("floor rooms", &key, &floor_statistics.rooms);
+            row(
+                "floor treasure chests",
+                &key,
+                &floor_statistics.treasure_chests,
+            );
+            row("floor spawn points", &key, &floor_statistics.spawns);
+            row("floor traps", &key, &floor_statistics.traps);
+        }
+        csv
+    }
+
+    /// A few short lines for the editor's panel: the totals, the floors and what's used most.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} rooms, {:.2} exits per room, {} without exits",
+                self.rooms,
+                self.average_links(),
+                self.rooms_without_exits.len()
+            ),
+            format!(
+                "{} spawn points, {} kinds of monster",
+                self.spawns,
+                self.monsters.len()
+            ),
+        ];
+        for (&floor, floor_statistics) in self.floors.iter() {
+            lines.push(format!(
+                "Floor {}: {} rooms, {} chests, {} spawn points, {} traps",
+                floor_name(floor),
+                floor_statistics.rooms,
+                floor_statistics.treasure_chests,
+                floor_statistics.spawns,
+                floor_statistics.traps
+            ));
+        }
+        lines.push(format!(
+            "Most used tiles: {}",
+            most_used(&self.tiles, |&tile| tile.to_string())
+        ));
+        lines.push(format!(
+            "Most used objects: {}",
+            most_used(&self.objects, |&object| RoomObject::from_byte(object)
+                .name()
+                .to_string())
+        ));
+        lines.push(format!(
+            "Traps: {}",
+            most_used(&self.traps, |kind| format!(
+                "'{}'",
+                kind.character() as char
+            ))
+        ));
+        lines
+    }
+}
+
+/// Floors are numbered from 1 on the starting room's floor, going down.
+fn floor_name(floor: Option<i32>) -> String {
+    match floor {
+        Some(floor) => (floor + 1).to_string(),
+        None => String::from("unreachable"),
+    }
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The five keys with the highest counts, like "21 x 340, 1 x 200".
+fn most_used<K>(counts: &BTreeMap<K, usize>, name: impl Fn(&K) -> String) -> String {
+    let mut sorted: Vec<(&K, &usize)> = counts.iter().collect();
+    sorted.sort_by_key(|&(_, &count)| std::cmp::Reverse(count));
+    let most: Vec<String> = sorted
+        .iter()
+        .take(5)
+        .map(|(key, count)| format!("{} x {}", name(key), count))
+        .collect();
+    if most.is_empty() {
+        String::from("none")
+    } else {
+        most.join(", ")
+    }
+}
+
+pub fn statistics(rooms: &[Room], start: RoomIndex) -> Statistics {
+    let floors = graph::floors_from(rooms, start);
+    let mut statistics = Statistics {
+        rooms: rooms.len(),
+        ..Statistics::default()
+    };
+    for (index, room) in rooms.iter().enumerate() {
+        let floor = statistics.floors.entry(floors[index]).or_default();
+        floor.rooms += 1;
+        for y in 0..ROOM_HEIGHT {
+            for x in 0..ROOM_WIDTH {
+                match room.get_tile(x, y) {
+                    Tile::Graphic(tile) => *statistics.tiles.entry(tile).or_default() += 1,
+                    Tile::Trap(kind) => {
+                        *statistics.traps.entry(kind).or_default() += 1;
+                        floor.traps += 1;
+                    }
+                }
+                match room.get_object(x, y) {
+                    RoomObject::None | RoomObject::Monster(_) => {}
+                    object => {
+                        *statistics.objects.entry(object.to_byte()).or_default() += 1;
+                        if object == RoomObject::TreasureChest {
+                            floor.treasure_chests += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let spawns = room.monster_spawns().len();
+        statistics.spawns += spawns;
+        floor.spawns += spawns;
+        if room.monster_id != 0 {
+            let usage = statistics.monsters.entry(room.monster_id).or_default();
+            usage.rooms += 1;
+            usage.spawns += spawns;
+            usage.count += room.monster_count as usize;
+        }
+
+        let exits = Direction::ALL
+            .iter()
+            .filter(|&&direction| room.link(direction).is_some())
+            .count();
+        statistics.links += exits;
+        if exits == 0 {
+            statistics.rooms_without_exits.push(index);
+        }
+    }
+    statistics
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "rooms                   {}", self.rooms)?;
+        writeln!(f, "links                   {}", self.links)?;
+        writeln!(f, "average links per room  {:.2}", self.average_links())?;
+        let without_exits: Vec<String> = self
+            .rooms_without_exits
+            .iter()
+            .map(|room| (room + 1).to_string())
+            .collect();
+        writeln!(
+            f,
+            "rooms without exits     {}  {}",
+            without_exits.len(),
+            without_exits.join(" ")
+        )?;
+        writeln!(f, "spawn points            {}", self.spawns)?;
+
+        writeln!(
+            f,
+            "\nfloor        rooms  treasure chests  spawn points  traps"
+        )?;
+        for (&floor, floor_statistics) in self.floors.iter() {
+            writeln!(
+                f,
+                "{:<11}  {:>5}  {:>15}  {:>12}  {:>5}",
+                floor_name(floor),
+                floor_statistics.rooms,
+                floor_statistics.treasure_chests,
+                floor_statistics.spawns,
+                floor_statistics.traps
+            )?;
+        }
+
+        writeln!(f, "\nmonster  rooms  spawn points  count")?;
+        for (id, usage) in self.monsters.iter() {
+            writeln!(
+                f,
+                "{:>7}  {:>5}  {:>12}  {:>5}",
+                id, usage.rooms, usage.spawns, usage.count
+            )?;
+        }
+
+        writeln!(f, "\ntrap  cells")?;
+        for (kind, count) in self.traps.iter() {
+            writeln!(f, "'{}'   {:>5}", kind.character() as char, count)?;
+        }
+
+        writeln!(f, "\nobject                        cells")?;
+        for (&object, count) in self.objects.iter() {
+            let object = RoomObject::from_byte(object);
+            writeln!(f, "{:<28}  {:>5}", object.to_string(), count)?;
+        }
+
+        writeln!(f, "\ntile  cells")?;
+        for (tile, count) in self.tiles.iter() {
+            writeln!(f, "{:>4}  {:>5}", tile, count)?;
+        }
+        Ok(())
+    }
+}
@@ -14,15 +14,1454 @@ fn crumb_basic_test() {
 fn pascal_from_pascal_string_test() {
     use super::pascal::from_pascal_string;
 
-    assert_eq!(from_pascal_string(b"\x0BHello world"), "Hello world");
-    assert_eq!(from_pascal_string(b"\x0AHello world"), "Hello worl");
+    assert_eq!(
+        from_pascal_string(b"\x0BHello world").unwrap(),
+        "Hello world"
+    );
+    assert_eq!(
+        from_pascal_string(b"\x0AHello world").unwrap(),
+        "Hello worl"
+    );
+    assert_eq!(from_pascal_string(b"\x0CHello world"), None);
+    // DOS code page 437, not UTF-8
+    assert_eq!(from_pascal_string(b"\x04\x82\xC9\xCD\x01").unwrap(), "é╔═☺");
+    assert_eq!(from_pascal_string(b""), None);
 }
 
 #[test]
 fn pascal_to_pascal_string_test() {
-    use super::pascal::to_pascal_string;
+    use super::pascal::{to_pascal_string, to_pascal_string_lossy, PascalError};
 
-    assert_eq!(to_pascal_string("", 0xFF), b"\x00");
-    assert_eq!(to_pascal_string("Hello world", 0x00), b"\x00");
-    assert_eq!(to_pascal_string("Hello world", 0xFF), b"\x0BHello world");
+    assert_eq!(to_pascal_string("", 0xFF).unwrap(), b"\x00");
+    assert_eq!(
+        to_pascal_string("Hello world", 0x00),
+        Err(PascalError::TooLong {
+            length: 11,
+            max_length: 0
+        })
+    );
+    assert_eq!(
+        to_pascal_string("Hello world", 0xFF).unwrap(),
+        b"\x0BHello world"
+    );
+    // Characters are counted, not UTF-8 bytes
+    assert_eq!(to_pascal_string("Café", 4).unwrap(), b"\x04Caf\x82");
+    assert_eq!(
+        to_pascal_string("Cafe\u{301}", 5),
+        Err(PascalError::Unrepresentable('\u{301}'))
+    );
+    assert_eq!(to_pascal_string_lossy("Cafe\u{301}", 4), b"\x04Cafe");
+    assert_eq!(to_pascal_string_lossy("日本", 4), b"\x02??");
+}
+
+/// Build an RMS record with every byte set to something distinctive so that round trip tests
+/// catch any byte that moves or gets zeroed.
+#[cfg(test)]
+fn synthetic_room_record(seed: u8, name: &[u8]) -> Vec<u8> {
+    let mut record: Vec<u8> = (0..0x168u32)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect();
+    // Name field, followed by leftover junk from a previous, longer name
+    record[0x14D] = name.len() as u8;
+    record[0x14E..0x14E + name.len()].copy_from_slice(name);
+    record
+}
+
+#[test]
+fn monster_load_monsters_truncated_test() {
+    use super::error::Error;
+    use super::monster::load_monsters;
+
+    let filename = std::env::temp_dir().join("rmsedit_truncated_test.dat");
+    let filename = filename.to_str().unwrap();
+    std::fs::write(filename, vec![0u8; 0x1F * 2 + 0x10]).unwrap();
+
+    match load_monsters(filename) {
+        Err(Error::TruncatedRecord {
+            record,
+            offset,
+            actual,
+            ..
+        }) => {
+            assert_eq!(record, 2);
+            assert_eq!(offset, 0x1F * 2);
+            assert_eq!(actual, 0x10);
+        }
+        _ => panic!("expected a truncated record"),
+    }
+    std::fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn rms_round_trip_test() {
+    use super::rms::Dungeon;
+
+    let mut rms_data = synthetic_room_record(1, b"Entrance");
+    rms_data.extend(synthetic_room_record(2, b""));
+    rms_data.extend(synthetic_room_record(3, b"The longest name, 26 chars"));
+
+    let dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    assert_eq!(dungeon.rooms.len(), 3);
+    assert_eq!(dungeon.rooms[0].name, "Entrance");
+    assert_eq!(dungeon.rooms[2].name, "The longest name, 26 chars");
+    assert!(dungeon.trailer.is_empty());
+    assert_eq!(dungeon.to_bytes(), rms_data);
+}
+
+#[test]
+fn rms_round_trip_trailer_test() {
+    use super::rms::Dungeon;
+
+    let mut rms_data = synthetic_room_record(7, b"Room");
+    rms_data.extend_from_slice(b"\x1A partial record");
+
+    let dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    assert_eq!(dungeon.rooms.len(), 1);
+    assert_eq!(dungeon.trailer, b"\x1A partial record");
+    assert_eq!(dungeon.to_bytes(), rms_data);
+}
+
+#[test]
+fn rms_rename_preserves_name_field_test() {
+    use super::rms::Dungeon;
+
+    let rms_data = synthetic_room_record(9, b"Long room name");
+    let mut dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    dungeon.rooms[0].name = String::from("Short");
+
+    let saved = dungeon.to_bytes();
+    assert_eq!(saved.len(), rms_data.len());
+    // Everything before the name is untouched
+    assert_eq!(saved[..0x14D], rms_data[..0x14D]);
+    assert_eq!(saved[0x14D..0x153], *b"\x05Short");
+    // So is everything after the new name
+    assert_eq!(saved[0x153..], rms_data[0x153..]);
+}
+
+#[test]
+fn rms_load_rooms_missing_file_test() {
+    use super::error::Error;
+    use super::rms::load_rooms;
+
+    match load_rooms("this file does not exist.rms") {
+        Err(Error::Io { filename, .. }) => assert_eq!(filename, "this file does not exist.rms"),
+        _ => panic!("expected an I/O error"),
+    }
+}
+
+/// Rooms with no exits and correct ids, for testing link manipulation.
+#[cfg(test)]
+fn unlinked_rooms(count: u8) -> Vec<super::rms::Room> {
+    let rms_data: Vec<u8> = (0..count)
+        .flat_map(|i| {
+            let mut record = synthetic_room_record(i, b"Room");
+            record[0x143..0x149].copy_from_slice(&[0; 6]);
+            record[0x149] = i + 1;
+            record
+        })
+        .collect();
+    super::rms::Dungeon::from_bytes("test", &rms_data)
+        .unwrap()
+        .rooms
+}
+
+#[test]
+fn rms_link_test() {
+    use super::rms::Direction;
+
+    let mut rooms = unlinked_rooms(2);
+    assert_eq!(rooms[0].link(Direction::North), None);
+
+    rooms[0].set_link(Direction::North, Some(1));
+    assert_eq!(rooms[0].link(Direction::North), Some(1));
+    // Only one side is touched
+    assert_eq!(rooms[1].link(Direction::South), None);
+
+    let rms_data = super::rms::Dungeon {
+        rooms,
+        trailer: vec![],
+    }
+    .to_bytes();
+    // Stored 1-based
+    assert_eq!(rms_data[0x143], 2);
+}
+
+#[test]
+fn rms_link_rooms_test() {
+    use super::rms::{link_rooms, unlink_rooms, Direction};
+
+    let mut rooms = unlinked_rooms(3);
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    assert_eq!(rooms[0].link(Direction::East), Some(1));
+    assert_eq!(rooms[1].link(Direction::West), Some(0));
+
+    // Relinking east to another room must not leave room 1 pointing back at room 0
+    link_rooms(&mut rooms, 0, Direction::East, 2);
+    assert_eq!(rooms[0].link(Direction::East), Some(2));
+    assert_eq!(rooms[2].link(Direction::West), Some(0));
+    assert_eq!(rooms[1].link(Direction::West), None);
+
+    link_rooms(&mut rooms, 2, Direction::Down, 1);
+    assert_eq!(rooms[1].link(Direction::Up), Some(2));
+
+    unlink_rooms(&mut rooms, 1, Direction::Up);
+    assert_eq!(rooms[1].link(Direction::Up), None);
+    assert_eq!(rooms[2].link(Direction::Down), None);
+    // Unrelated links survive
+    assert_eq!(rooms[2].link(Direction::West), Some(0));
+}
+
+#[test]
+fn validate_clean_test() {
+    use super::rms::{link_rooms, Direction};
+    use super::validate::validate;
+
+    let mut rooms = unlinked_rooms(3);
+    link_rooms(&mut rooms, 0, Direction::North, 1);
+    link_rooms(&mut rooms, 1, Direction::Up, 2);
+    assert_eq!(validate(&rooms), vec![]);
+}
+
+#[test]
+fn validate_problems_test() {
+    use super::rms::{link_rooms, Direction};
+    use super::validate::{has_errors, validate, Field, Severity};
+
+    let mut rooms = unlinked_rooms(5);
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    // One-way
+    rooms[1].set_link(Direction::North, Some(2));
+    // Past the end
+    rooms[2].set_link(Direction::South, Some(9));
+    // Self link
+    rooms[0].set_link(Direction::Down, Some(0));
+    // Room 4 (index 3) and 5 (index 4) are only linked to each other
+    link_rooms(&mut rooms, 3, Direction::West, 4);
+    // Can't be written in the DOS character set
+    rooms[4].name = String::from("Ω and ✓");
+
+    let issues = validate(&rooms);
+    let summary: Vec<_> = issues
+        .iter()
+        .map(|issue| (issue.severity, issue.room, issue.field))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Severity::Warning, 0, Field::Nav(Direction::Down)),
+            (Severity::Warning, 1, Field::Nav(Direction::North)),
+            (Severity::Error, 2, Field::Nav(Direction::South)),
+            (Severity::Warning, 3, Field::Room),
+            (Severity::Error, 4, Field::Name),
+            (Severity::Warning, 4, Field::Room),
+        ]
+    );
+    assert!(has_errors(&issues));
+}
+
+#[test]
+fn rms_append_duplicate_room_test() {
+    use super::rms::{append_room, duplicate_room, link_rooms, Direction, Room};
+    use super::validate::validate;
+
+    let mut rooms = unlinked_rooms(2);
+    link_rooms(&mut rooms, 0, Direction::North, 1);
+
+    assert_eq!(append_room(&mut rooms, Room::new("Blank")), Some(2));
+    assert_eq!(rooms[2].id(), 3);
+    assert_eq!(rooms[2].name, "Blank");
+
+    assert_eq!(duplicate_room(&mut rooms, 1), Some(3));
+    assert_eq!(rooms[3].id(), 4);
+    assert_eq!(rooms[3].link(Direction::South), None);
+
+    link_rooms(&mut rooms, 1, Direction::North, 2);
+    link_rooms(&mut rooms, 2, Direction::North, 3);
+    assert_eq!(validate(&rooms), vec![]);
+}
+
+#[test]
+fn rms_delete_room_test() {
+    use super::rms::{delete_room, link_rooms, Direction};
+    use super::validate::validate;
+
+    let mut rooms = unlinked_rooms(4);
+    link_rooms(&mut rooms, 0, Direction::North, 1);
+    link_rooms(&mut rooms, 1, Direction::North, 2);
+    link_rooms(&mut rooms, 0, Direction::East, 3);
+
+    delete_room(&mut rooms, 1);
+    assert_eq!(rooms.len(), 3);
+    assert_eq!(rooms[0].link(Direction::North), None);
+    assert_eq!(rooms[0].link(Direction::East), Some(2));
+    assert_eq!(rooms[2].link(Direction::West), Some(0));
+    assert_eq!(rooms[1].link(Direction::South), None);
+    assert_eq!(rooms.iter().map(|r| r.id()).collect::<Vec<_>>(), [1, 2, 3]);
+
+    // The old room 3 is now cut off, but nothing points at the wrong room
+    let issues = validate(&rooms);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].room, 1);
+}
+
+#[test]
+fn rms_move_room_test() {
+    use super::rms::{link_rooms, move_room, Direction};
+    use super::validate::validate;
+
+    let mut rooms = unlinked_rooms(4);
+    link_rooms(&mut rooms, 0, Direction::North, 1);
+    link_rooms(&mut rooms, 1, Direction::North, 2);
+    link_rooms(&mut rooms, 2, Direction::North, 3);
+    rooms[3].name = String::from("Top");
+
+    move_room(&mut rooms, 3, 1);
+    assert_eq!(rooms[1].name, "Top");
+    assert_eq!(rooms[0].link(Direction::North), Some(2));
+    assert_eq!(rooms[2].link(Direction::North), Some(3));
+    assert_eq!(rooms[3].link(Direction::North), Some(1));
+    assert_eq!(rooms[1].link(Direction::South), Some(3));
+    assert_eq!(validate(&rooms), vec![]);
+
+    move_room(&mut rooms, 0, 3);
+    assert_eq!(rooms[3].link(Direction::North), Some(1));
+    assert_eq!(validate(&rooms), vec![]);
+}
+
+#[test]
+fn rms_trap_tile_test() {
+    use super::rms::{Dungeon, Tile, TrapKind};
+
+    let mut rms_data = synthetic_room_record(0, b"Traps");
+    rms_data[1] = 21;
+    rms_data[2] = b'U';
+    rms_data[3] = b'x';
+    let mut dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    let room = &mut dungeon.rooms[0];
+
+    assert_eq!(room.get_tile(0, 0), Tile::Graphic(21));
+    assert_eq!(
+        room.get_tile(1, 0),
+        Tile::Trap(TrapKind::new(b'U').unwrap())
+    );
+    assert_eq!(
+        room.get_tile(2, 0),
+        Tile::Trap(TrapKind::new(b'x').unwrap())
+    );
+    assert_ne!(room.get_tile(1, 0), room.get_tile(2, 0));
+    assert_eq!(room.get_tile(2, 0).graphic(), 21);
+    assert_eq!(TrapKind::new(84), None);
+
+    // Painting a trap back over itself doesn't change the record
+    let trap = room.get_tile(2, 0);
+    room.set_tile(2, 0, trap);
+    assert_eq!(dungeon.to_bytes(), rms_data);
+
+    let mut kind = TrapKind::default();
+    for _ in 0..(b'~' - b'U' + 1) {
+        kind = kind.next();
+    }
+    assert_eq!(kind, TrapKind::default());
+}
+
+#[test]
+fn object_round_trip_test() {
+    use super::object::RoomObject;
+
+    for code in 0..=255u8 {
+        assert_eq!(RoomObject::from_byte(code).to_byte(), code);
+    }
+    assert_eq!(RoomObject::from_byte(0), RoomObject::None);
+    assert_eq!(RoomObject::from_byte(b'a'), RoomObject::Monster(b'a'));
+    assert_eq!(RoomObject::from_byte(b'e'), RoomObject::TreasureChest);
+    assert_eq!(RoomObject::from_byte(b'w'), RoomObject::StoneMarker);
+    assert_eq!(RoomObject::from_byte(b'x'), RoomObject::Unknown(b'x'));
+    // Every code from 'd' to 'w' is known
+    for code in b'd'..=b'w' {
+        assert_eq!(
+            RoomObject::PLACEABLE[(code - b'd') as usize].to_byte(),
+            code
+        );
+    }
+}
+
+#[test]
+fn object_next_test() {
+    use super::object::RoomObject;
+
+    let mut object = RoomObject::None;
+    for expected in RoomObject::PLACEABLE.iter() {
+        object = object.next();
+        assert_eq!(object, *expected);
+    }
+    assert_eq!(object.next(), RoomObject::None);
+}
+
+#[test]
+fn validate_monsters_test() {
+    use super::monster::Monster;
+    use super::object::RoomObject;
+    use super::validate::{validate_monsters, Field};
+
+    let monsters = vec![Monster { gfx_id: 1 }, Monster { gfx_id: 2 }];
+    let mut rooms = unlinked_rooms(3);
+    for room in rooms.iter_mut() {
+        for y in 0..super::rms::ROOM_HEIGHT {
+            for x in 0..super::rms::ROOM_WIDTH {
+                room.set_object(x, y, RoomObject::None);
+            }
+        }
+        room.monster_count = 0;
+    }
+    rooms[0].monster_id = 2;
+    rooms[0].monster_count = 3;
+    rooms[0].set_object(4, 5, RoomObject::MONSTER_SPAWN);
+    rooms[1].monster_id = 3;
+    rooms[2].monster_id = 0;
+    rooms[2].set_object(1, 1, RoomObject::MONSTER_SPAWN);
+
+    assert_eq!(rooms[0].monster_index(), Some(1));
+    assert_eq!(rooms[0].monster_spawns(), vec![(4, 5)]);
+    assert_eq!(rooms[2].monster_index(), None);
+
+    let issues = validate_monsters(&rooms, &monsters);
+    assert_eq!(
+        issues
+            .iter()
+            .map(|issue| (issue.room, issue.field))
+            .collect::<Vec<_>>(),
+        vec![(1, Field::MonsterId), (2, Field::MonsterId)]
+    );
+}
+
+#[test]
+fn analysis_unknowns_test() {
+    use super::analysis::analyze_unknowns;
+
+    let mut rooms = unlinked_rooms(4);
+    for (i, room) in rooms.iter_mut().enumerate() {
+        room.unknown_a = if i < 3 { 7 } else { 9 };
+        room.unknown_b = 0;
+        room.monster_id = i as u8 * 2;
+        room.unknown_c = i as u8;
+    }
+
+    let reports = analyze_unknowns(&rooms);
+    assert_eq!(reports.len(), 4);
+
+    let a = &reports[0];
+    assert_eq!(a.name, "unknown_a");
+    assert_eq!(a.values.len(), 2);
+    assert_eq!(a.values[0].value, 7);
+    assert_eq!(a.values[0].rooms, vec![0, 1, 2]);
+    assert_eq!(a.values[1].rooms, vec![3]);
+    // Only room 1 is reachable since nothing is linked
+    assert_eq!(a.values[0].mean_depth, Some(0.0));
+    assert_eq!(a.values[1].mean_depth, None);
+
+    let correlation = |report: &super::analysis::FieldReport, name: &str| {
+        report
+            .correlations
+            .iter()
+            .find(|(property, _)| *property == name)
+            .unwrap()
+            .1
+    };
+    // Never changes, so nothing to correlate
+    assert_eq!(correlation(&reports[1], "monster id"), None);
+    let c = correlation(&reports[2], "monster id").unwrap();
+    assert!((c - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn json_parse_test() {
+    use super::json::{parse, Value};
+
+    let value = parse(r#" {"a": [1, -2.5, "x\nA"], "b": {}, "c": null, "d": true} "#).unwrap();
+    assert_eq!(value.get("a").unwrap().as_array().unwrap().len(), 3);
+    assert_eq!(
+        value.get("a").unwrap().as_array().unwrap()[2],
+        Value::String(String::from("x\nA"))
+    );
+    assert_eq!(value.get("c"), Some(&Value::Null));
+    assert_eq!(parse(&value.to_pretty_string()).unwrap(), value);
+
+    assert!(parse("[1, 2").unwrap_err().starts_with("line 1 column 6"));
+    assert!(parse("{} {}").is_err());
+    // Too deep to parse without running out of stack, which is an error rather than a crash
+    assert!(parse(&"[".repeat(100_000))
+        .unwrap_err()
+        .ends_with("nested too deeply"));
+    assert!(parse(&format!("{}{}", "[".repeat(128), "]".repeat(128))).is_ok());
+}
+
+#[test]
+fn rms_json_round_trip_test() {
+    use super::rms::Dungeon;
+    use super::rms_json::{from_json, to_json};
+
+    let mut rms_data = synthetic_room_record(1, b"Entrance");
+    rms_data.extend(synthetic_room_record(2, b"Crypt \"of\" doom"));
+    rms_data.extend_from_slice(b"\x1A");
+    let dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+
+    let json = to_json(&dungeon);
+    let imported = from_json("test.json", &json).unwrap();
+    assert_eq!(imported.to_bytes(), rms_data);
+}
+
+#[test]
+fn rms_json_refuse_test() {
+    use super::error::Error;
+    use super::rms::Dungeon;
+    use super::rms_json::{from_json, to_json};
+
+    let rms_data = synthetic_room_record(1, b"Entrance");
+    let json = to_json(&Dungeon::from_bytes("test", &rms_data).unwrap());
+    let refuse = |json: String, expected: &str| match from_json("test.json", &json) {
+        Err(Error::Format { message, .. }) => assert!(
+            message.contains(expected),
+            "\"{}\" doesn't mention \"{}\"",
+            message,
+            expected
+        ),
+        _ => panic!("expected \"{}\" to be refused", expected),
+    };
+
+    refuse(
+        json.replace("\"Entrance\"", "\"This name is much too long for a room\""),
+        "room 1: name: 37 characters is longer than the 26 that fit",
+    );
+    refuse(
+        json.replacen("\"monster_id\": ", "\"monster_id\": 1", 1),
+        "\"monster_id\" must be a whole number from 0 to 255",
+    );
+    refuse(
+        json.replace("\"tiles\": [", "\"tiles\": [[0], "),
+        "\"tiles\" must have 8 rows",
+    );
+    refuse(
+        json.replace("\"trailer\": \"\"", "\"trailer\": \"0\""),
+        "hex",
+    );
+    refuse(json.replace("rmsedit dungeon", "something else"), "not an");
+}
+
+#[test]
+fn rms_text_round_trip_test() {
+    use super::rms::{Dungeon, Tile};
+    use super::rms_text::{from_text, to_text};
+
+    let mut rms_data = synthetic_room_record(1, b"Entrance");
+    rms_data.extend(synthetic_room_record(2, b" \"Odd\\ name\"\n"));
+    rms_data.extend_from_slice(b"\x1A");
+    let mut dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    // A room without leftovers in its name field, which shouldn't mention it
+    let mut clean = super::rms::Room::new("Clean");
+    for x in 0..super::rms::ROOM_WIDTH {
+        clean.set_tile(x, 0, Tile::from_byte(x as u8 * 13));
+    }
+    dungeon.rooms.push(clean);
+
+    let text = to_text(&dungeon);
+    assert_eq!(text.matches("name_field:").count(), 2);
+    // The bytes that aren't printable ASCII get a key at the top
+    assert!(text.contains("\n# \u{101}=1 \u{102}=2 "));
+    assert!(text.contains(" \u{10D}=13 "));
+    let imported = from_text("test.txt", &text).unwrap();
+    assert_eq!(imported.to_bytes(), dungeon.to_bytes());
+}
+
+#[test]
+fn rms_text_refuse_test() {
+    use super::error::Error;
+    use super::rms::{Dungeon, Room};
+    use super::rms_text::{from_text, to_text};
+
+    let text = to_text(&Dungeon {
+        rooms: vec![Room::new("Entrance")],
+        trailer: vec![],
+    });
+    let refuse = |text: String, expected: &str| match from_text("test.txt", &text) {
+        Err(Error::Format { message, .. }) => assert!(
+            message.contains(expected),
+            "\"{}\" doesn't mention \"{}\"",
+            message,
+            expected
+        ),
+        _ => panic!("expected \"{}\" to be refused", expected),
+    };
+
+    refuse(
+        text.replacen("....................", "...................", 1),
+        "line 5: tiles rows must be 20 characters wide",
+    );
+    refuse(
+        text.replace("\"Entrance\"", "\"This name is much too long for a room\""),
+        "name: 37 characters is longer than the 26 that fit",
+    );
+    refuse(text.replace("north: -", "north: 0"), "north must be a room");
+    refuse(
+        text.replace("count: 0", "count: 256"),
+        "count must be a number",
+    );
+    refuse(text.replace("room 1", "room 2"), "expected \"room 1\"");
+    refuse(text.replace("unknown_d: 0\n", ""), "unexpected end of file");
+}
+
+#[test]
+fn diff_test() {
+    use super::diff::{diff, Change, RoomDiff};
+    use super::object::RoomObject;
+    use super::rms::{Direction, Dungeon, Room, Tile, TrapKind};
+
+    let mut rms_data = synthetic_room_record(1, b"Entrance");
+    rms_data.extend(synthetic_room_record(2, b"Crypt"));
+    rms_data.extend(synthetic_room_record(3, b"Removed"));
+    let old = Dungeon::from_bytes("test", &rms_data).unwrap();
+    let mut new = Dungeon::from_bytes("test", &rms_data).unwrap();
+    assert!(diff(&old, &new).is_empty());
+
+    new.rooms[1].name = String::from("Tomb");
+    new.rooms[1].monster_count = 9;
+    new.rooms[1].set_link(Direction::Up, None);
+    new.rooms[1].unknown_c = 0xAB;
+    new.rooms[1].set_tile(3, 4, Tile::Trap(TrapKind::default()));
+    new.rooms[1].set_object(19, 7, RoomObject::TreasureChest);
+    new.rooms.truncate(2);
+    new.rooms.push(Room::new("Added"));
+    new.rooms.push(Room::new("Added too"));
+    new.trailer = vec![0x1A];
+
+    let differences = diff(&old, &new);
+    assert_eq!(differences.rooms.len(), 3);
+    match &differences.rooms[0] {
+        RoomDiff::Changed { room, changes, .. } => {
+            assert_eq!(*room, 1);
+            assert_eq!(
+                changes[0],
+                Change::Name {
+                    old: String::from("Crypt"),
+                    new: String::from("Tomb")
+                }
+            );
+            // The leftovers under the name didn't change
+            assert!(!matches!(changes[1], Change::NameField { .. }));
+            assert_eq!(changes.len(), 6);
+            assert_eq!(
+                changes[4],
+                Change::Tile {
+                    x: 3,
+                    y: 4,
+                    old: old.rooms[1].get_tile(3, 4),
+                    new: Tile::Trap(TrapKind::default())
+                }
+            );
+        }
+        _ => panic!("expected room 2 to change"),
+    }
+    assert!(matches!(
+        differences.rooms[1],
+        RoomDiff::Changed { room: 2, .. }
+    ));
+    assert!(matches!(
+        differences.rooms[2],
+        RoomDiff::Added { room: 3, .. }
+    ));
+    assert_eq!(differences.trailer, Some((vec![], vec![0x1A])));
+
+    let text = differences.to_string();
+    assert!(text.contains("room 2 \"Tomb\"\n  name: \"Crypt\" -> \"Tomb\"\n"));
+    assert!(text.contains("  tile (3, 4): "));
+    assert!(text.contains("room 4 \"Added too\": added\n"));
+    let json = super::json::parse(&differences.to_json()).unwrap();
+    assert_eq!(json.get("rooms").unwrap().as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn patch_dungeon_test() {
+    use super::diff::{diff, DungeonDiff, RoomDiff};
+    use super::object::RoomObject;
+    use super::patch::{patch_dungeon, reverse_diff};
+    use super::rms::{Direction, Dungeon, Room};
+
+    let mut rms_data = synthetic_room_record(1, b"Entrance");
+    rms_data.extend(synthetic_room_record(2, b"Crypt"));
+    rms_data.extend(synthetic_room_record(3, b"Removed"));
+    let original = Dungeon::from_bytes("test", &rms_data).unwrap();
+    let mut modded = Dungeon::from_bytes("test", &rms_data).unwrap();
+    modded.rooms[1].name = String::from("A much longer name");
+    modded.rooms[1].set_object(0, 0, RoomObject::OldBones);
+    modded.rooms[1].unknown_b = 0x42;
+    modded.rooms.truncate(2);
+    modded.trailer = vec![0x1A];
+
+    let mod_diff = diff(&original, &modded);
+    // Someone else's edit to a field the mod doesn't touch
+    let mut edited = Dungeon::from_bytes("test", &rms_data).unwrap();
+    edited.rooms[0].set_link(Direction::North, None);
+    let patched = patch_dungeon(&edited, &mod_diff).unwrap();
+    assert_eq!(patched.rooms.len(), 2);
+    assert_eq!(patched.rooms[1].name, "A much longer name");
+    assert_eq!(patched.rooms[0].link(Direction::North), None);
+
+    let reverted = patch_dungeon(
+        &patch_dungeon(&original, &mod_diff).unwrap(),
+        &reverse_diff(&mod_diff),
+    );
+    assert_eq!(reverted.unwrap().to_bytes(), rms_data);
+
+    // The base doesn't have what the mod changes
+    let mut conflicting = Dungeon::from_bytes("test", &rms_data).unwrap();
+    conflicting.rooms[1].unknown_b = 0;
+    let conflicts = patch_dungeon(&conflicting, &mod_diff).err().unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert!(conflicts[0].starts_with("room 2: can't apply \"unknown_b"));
+    conflicting.rooms.push(Room::new("Extra"));
+    assert!(patch_dungeon(&conflicting, &mod_diff).is_err());
+
+    // Hand edited patches that don't add or remove rooms at the end are refused, not applied
+    let two_rooms = Dungeon::from_bytes("test", &rms_data[..2 * 0x168]).unwrap();
+    let removed = |room| RoomDiff::Removed {
+        room,
+        contents: Room::new("Removed"),
+    };
+    let gap = DungeonDiff {
+        rooms: vec![removed(0), removed(9)],
+        trailer: None,
+    };
+    assert!(patch_dungeon(&two_rooms, &gap).is_err());
+    let mixed = DungeonDiff {
+        rooms: vec![
+            removed(1),
+            RoomDiff::Added {
+                room: 2,
+                contents: Room::new("Added"),
+            },
+        ],
+        trailer: None,
+    };
+    assert_eq!(
+        patch_dungeon(&two_rooms, &mixed).err().unwrap(),
+        vec!["adds and removes rooms at the same time"]
+    );
+    let added = DungeonDiff {
+        rooms: vec![
+            RoomDiff::Added {
+                room: 3,
+                contents: Room::new("Fourth"),
+            },
+            RoomDiff::Added {
+                room: 2,
+                contents: Room::new("Third"),
+            },
+        ],
+        trailer: None,
+    };
+    let patched = patch_dungeon(&two_rooms, &added).unwrap();
+    assert_eq!(patched.rooms[2].name, "Third");
+    assert_eq!(patched.rooms[3].name, "Fourth");
+}
+
+#[test]
+fn patch_files_test() {
+    use super::patch::{patch_bytes, ByteChange, Patch};
+    use super::rms::Dungeon;
+
+    let dir = std::env::temp_dir().join("rmsedit_patch_test");
+    let original_dir = dir.join("original");
+    let modded_dir = dir.join("modded");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&original_dir).unwrap();
+    std::fs::create_dir_all(&modded_dir).unwrap();
+
+    let rms_data = synthetic_room_record(1, b"Entrance");
+    let mut dungeon = Dungeon::from_bytes("test", &rms_data).unwrap();
+    dungeon.rooms[0].monster_id = 7;
+    let pic = vec![1u8, 2, 3, 4, 5, 6];
+    let modded_pic = vec![1u8, 9, 3, 4, 5, 6, 7, 8];
+    std::fs::write(original_dir.join("DUNGEON.RMS"), &rms_data).unwrap();
+    std::fs::write(modded_dir.join("DUNGEON.RMS"), dungeon.to_bytes()).unwrap();
+    std::fs::write(original_dir.join("EGAPICS.PIC"), &pic).unwrap();
+    std::fs::write(modded_dir.join("EGAPICS.PIC"), &modded_pic).unwrap();
+    std::fs::write(original_dir.join("PYMON.DAT"), b"same").unwrap();
+    std::fs::write(modded_dir.join("PYMON.DAT"), b"same").unwrap();
+
+    let patch = Patch::create(&original_dir, &modded_dir).unwrap();
+    assert_eq!(patch.files.len(), 2);
+    let patch = Patch::from_json("test.patch", &patch.to_json()).unwrap();
+    // Applies once, but not twice
+    assert!(patch.check(&original_dir).unwrap().is_empty());
+    assert!(patch.apply(&original_dir).unwrap().is_empty());
+    assert_eq!(
+        std::fs::read(original_dir.join("EGAPICS.PIC")).unwrap(),
+        modded_pic
+    );
+    assert_eq!(
+        std::fs::read(original_dir.join("DUNGEON.RMS")).unwrap(),
+        dungeon.to_bytes()
+    );
+    assert_eq!(patch.check(&original_dir).unwrap().len(), 2);
+
+    assert!(patch.reversed().apply(&original_dir).unwrap().is_empty());
+    assert_eq!(
+        std::fs::read(original_dir.join("EGAPICS.PIC")).unwrap(),
+        pic
+    );
+    assert_eq!(
+        std::fs::read(original_dir.join("DUNGEON.RMS")).unwrap(),
+        rms_data
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Changes out of order, overlapping, past the end, or changing length before the last one
+    let change = |offset: usize, old: &[u8], new: &[u8]| ByteChange {
+        offset,
+        old: old.to_vec(),
+        new: new.to_vec(),
+    };
+    let bad = [
+        vec![change(3, b"4", b"9"), change(1, b"2", b"9")],
+        vec![change(1, b"23", b"99"), change(2, b"3", b"9")],
+        vec![change(5, b"67", b"99")],
+        vec![change(usize::MAX, b"1", b"9")],
+        vec![change(1, b"2", b"99"), change(3, b"4", b"9")],
+    ];
+    for changes in bad.iter() {
+        assert_eq!(patch_bytes(&pic, pic.len(), changes).unwrap_err().len(), 1);
+    }
+}
+
+#[test]
+fn layout_test() {
+    use super::layout::{layout, Position};
+    use super::rms::{link_rooms, Direction};
+
+    let mut rooms = unlinked_rooms(7);
+    // A square of four rooms, with a ladder down from the first
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    link_rooms(&mut rooms, 1, Direction::South, 2);
+    link_rooms(&mut rooms, 2, Direction::West, 3);
+    link_rooms(&mut rooms, 3, Direction::North, 0);
+    link_rooms(&mut rooms, 0, Direction::Down, 4);
+    // Room 6 claims to be east of room 4, where room 3 already is
+    link_rooms(&mut rooms, 3, Direction::East, 5);
+
+    let map = layout(&rooms, 0);
+    let at = |x, y, floor| Position { x, y, floor };
+    assert_eq!(map.positions[0], at(0, 0, 0));
+    assert_eq!(map.positions[1], at(1, 0, 0));
+    assert_eq!(map.positions[2], at(1, 1, 0));
+    assert_eq!(map.positions[3], at(0, 1, 0));
+    assert_eq!(map.positions[4], at(0, 0, 1));
+    assert_ne!(map.positions[5], map.positions[2]);
+    assert_eq!(map.contradictions, vec![(3, Direction::East)]);
+    assert!(map.is_contradicted(&rooms, 5));
+    assert!(!map.is_contradicted(&rooms, 1));
+    // Room 7 isn't linked to anything so it goes off to the side
+    assert!(map.positions[6].x > 1);
+    assert_eq!(map.floors(), vec![0, 1]);
+}
+
+#[test]
+fn png_test() {
+    use super::img::{Color, Image};
+    use super::png::{adler32, crc32, encode};
+
+    assert_eq!(crc32(b"IEND"), 0xAE426082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+
+    let image = Image {
+        width: 3,
+        height: 2,
+        pixels: vec![Color::rgb(0xFF, 0x00, 0x00); 6],
+    };
+    let png = encode(&image);
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1A\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], b"\x00\x00\x00\x03\x00\x00\x00\x02");
+    assert_eq!(
+        &png[png.len() - 12..],
+        b"\x00\x00\x00\x00IEND\xAE\x42\x60\x82"
+    );
+}
+
+#[test]
+fn contact_sheet_test() {
+    use super::img::{Color, Image};
+    use super::object::RoomObject;
+    use super::rms::Tile;
+    use super::sheet::contact_sheet;
+
+    let solid = |color| Image {
+        width: 15,
+        height: 15,
+        pixels: vec![color; 15 * 15],
+    };
+    let tiles: Vec<Image> = (0..100)
+        .map(|i| solid(Color::rgb(i as u8, 0x00, 0x00)))
+        .collect();
+    let mut rooms = unlinked_rooms(5);
+    rooms[1].set_tile(0, 0, Tile::Graphic(5));
+    rooms[1].set_object(0, 0, RoomObject::None);
+    rooms[1].set_object(1, 0, RoomObject::from_byte(b'z'));
+
+    let mut labels = Vec::new();
+    let sheet = contact_sheet(&rooms, &tiles, &[], &[], 2, &mut |text, color| {
+        labels.push(text.to_string());
+        solid(color)
+    });
+    // Two columns and three rows of 300x120 rooms with labels and padding
+    assert_eq!(sheet.width, 8 + 2 * 308);
+    assert_eq!(sheet.height, 8 + 3 * 148);
+    assert_eq!(sheet.pixels.len(), sheet.width * sheet.height);
+    // Room 2's first tile is in the second column
+    assert_eq!(sheet.pixels[8 * sheet.width + 8 + 308], tiles[4].pixels[0]);
+    // Objects without sprites are marked
+    assert!(labels.contains(&"z".to_string()));
+    assert!(labels.iter().any(|label| label.starts_with("5: ")));
+}
+
+#[test]
+fn room_transfer_test() {
+    use super::rms::{link_rooms, paste_room, Direction, Tile};
+    use super::rms_text::{room_from_text, room_to_text};
+
+    let mut source = unlinked_rooms(3);
+    link_rooms(&mut source, 2, Direction::North, 0);
+    source[2].name = String::from("Copied");
+    source[2].set_tile(3, 4, Tile::Graphic(7));
+    source[2].monster_id = 5;
+
+    let text = room_to_text(3, &source[2]);
+    assert!(text.starts_with("rmsedit room 1\n"));
+    let copy = room_from_text("copy.room", &text).unwrap();
+    assert_eq!(room_to_text(3, &copy), text);
+    assert!(room_from_text("copy.room", &format!("{}\n{}", text, text)).is_err());
+    assert!(room_from_text("copy.room", "rmsedit dungeon 1\n").is_err());
+
+    let mut destination = unlinked_rooms(2);
+    link_rooms(&mut destination, 0, Direction::East, 1);
+
+    // Without links the room keeps its own exits and id
+    paste_room(&mut destination, 0, &copy, false);
+    assert_eq!(destination[0].name, "Copied");
+    assert_eq!(destination[0].get_tile(3, 4), Tile::Graphic(7));
+    assert_eq!(destination[0].monster_id, 5);
+    assert_eq!(destination[0].id(), 1);
+    assert_eq!(destination[0].link(Direction::East), Some(1));
+    assert_eq!(destination[0].link(Direction::North), None);
+
+    // With links the copied exits replace them
+    paste_room(&mut destination, 1, &copy, true);
+    assert_eq!(destination[1].id(), 2);
+    assert_eq!(destination[1].link(Direction::North), Some(0));
+    assert_eq!(destination[1].link(Direction::West), None);
+
+    // Exits past the end of this dungeon are dropped
+    let mut far = copy.clone();
+    far.set_link(Direction::South, Some(9));
+    paste_room(&mut destination, 1, &far, true);
+    assert_eq!(destination[1].link(Direction::South), None);
+}
+
+#[test]
+fn replace_test() {
+    use super::object::RoomObject;
+    use super::replace::{preview, replace, Region, Replacement, Scope};
+    use super::rms::Tile;
+
+    let mut rooms: Vec<super::rms::Room> = (0..3).map(|_| super::rms::Room::new("Room")).collect();
+    rooms[0].set_tile(0, 0, Tile::Graphic(3));
+    rooms[0].set_tile(5, 5, Tile::Graphic(3));
+    rooms[2].set_tile(1, 1, Tile::Graphic(3));
+    rooms[1].set_object(2, 2, RoomObject::TreasureChest);
+    rooms[1].monster_id = 4;
+
+    let walls = Replacement::Tile {
+        from: Tile::Graphic(3),
+        to: Tile::Graphic(9),
+    };
+    assert_eq!(
+        preview(&rooms, walls, &Scope::everywhere()),
+        vec![(0, 2), (2, 1)]
+    );
+    // Only the top left corner of the first room
+    let corner = Scope {
+        rooms: Some(vec![0, 1]),
+        region: Region {
+            left: 0,
+            top: 0,
+            right: 3,
+            bottom: 3,
+        },
+    };
+    assert_eq!(replace(&mut rooms, walls, &corner), vec![(0, 1)]);
+    assert_eq!(rooms[0].get_tile(0, 0), Tile::Graphic(9));
+    assert_eq!(rooms[0].get_tile(5, 5), Tile::Graphic(3));
+    assert_eq!(rooms[2].get_tile(1, 1), Tile::Graphic(3));
+
+    let chests = Replacement::Object {
+        from: RoomObject::TreasureChest,
+        to: RoomObject::None,
+    };
+    assert_eq!(
+        replace(&mut rooms, chests, &Scope::everywhere()),
+        vec![(1, 1)]
+    );
+    assert_eq!(rooms[1].get_object(2, 2), RoomObject::None);
+
+    let monsters = Replacement::Monster { from: 4, to: 7 };
+    assert_eq!(replace(&mut rooms, monsters, &corner), vec![(1, 1)]);
+    assert_eq!(rooms[1].monster_id, 7);
+    // Replacing something with itself changes nothing
+    let nothing = Replacement::Monster { from: 7, to: 7 };
+    assert!(preview(&rooms, nothing, &Scope::everywhere()).is_empty());
+}
+
+#[test]
+fn query_test() {
+    use super::object::RoomObject;
+    use super::query::parse;
+    use super::rms::{link_rooms, Direction, Tile};
+
+    let mut rooms = unlinked_rooms(4);
+    for (i, room) in rooms.iter_mut().enumerate() {
+        for y in 0..super::rms::ROOM_HEIGHT {
+            for x in 0..super::rms::ROOM_WIDTH {
+                room.set_tile(x, y, Tile::Graphic(1));
+                room.set_object(x, y, RoomObject::None);
+            }
+        }
+        room.monster_id = 0;
+        room.monster_count = 0;
+        room.name = format!("Room {}", i + 1);
+    }
+    rooms[0].name = String::from("The Crypt");
+    rooms[0].set_object(1, 1, RoomObject::TreasureChest);
+    rooms[1].set_object(1, 1, RoomObject::TreasureChest);
+    rooms[1].set_object(2, 2, RoomObject::MONSTER_SPAWN);
+    rooms[1].monster_id = 7;
+    rooms[1].monster_count = 3;
+    for x in 0..5 {
+        rooms[2].set_tile(x, 0, Tile::Graphic(21));
+        rooms[2].set_object(x, 1, RoomObject::SoftSectionOfWall);
+    }
+    rooms[2].set_tile(6, 0, Tile::from_byte(b'U'));
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    link_rooms(&mut rooms, 1, Direction::Down, 2);
+    rooms[3].set_link(Direction::North, Some(0));
+
+    let find = |query: &str| parse(query).unwrap().filter(&rooms);
+    assert_eq!(find("object \"chest\" and spawns = 0"), vec![0]);
+    assert_eq!(find("object 'e' and not monster"), vec![0]);
+    assert_eq!(find("monster = 7"), vec![1]);
+    assert_eq!(find("count >= 3 or name ~ \"crypt\""), vec![0, 1]);
+    assert_eq!(find("name ~ \"room ?\""), vec![1, 2, 3]);
+    assert_eq!(find("name ~ \"*CRYPT\""), vec![0]);
+    assert_eq!(find("object 'k' > 4"), vec![2]);
+    assert_eq!(find("tile 21 = 5 and trap 'U' and trap = 1"), vec![2]);
+    assert_eq!(find("exits >= 2"), vec![1]);
+    assert_eq!(find("exit down or exit up"), vec![1, 2]);
+    assert_eq!(find("links-to 1"), vec![1, 3]);
+    assert_eq!(find("one-way"), vec![3]);
+    assert_eq!(find("not (exits or spawns)"), Vec::<usize>::new());
+
+    let error = |query: &str| parse(query).unwrap_err().column;
+    assert_eq!(error("monster >"), 10);
+    assert_eq!(error("tile 21 and"), 12);
+    assert_eq!(error("exits > 2 banana"), 11);
+    assert_eq!(error("name ~ \"crypt"), 8);
+    assert_eq!(error("(exit north"), 12);
+    assert_eq!(error("trap 21"), 6);
+}
+
+#[test]
+fn stats_test() {
+    use super::object::RoomObject;
+    use super::rms::{link_rooms, Direction, Room, Tile};
+    use super::stats::statistics;
+
+    let mut rooms: Vec<Room> = (0..4).map(|_| Room::new("Room")).collect();
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    link_rooms(&mut rooms, 1, Direction::Down, 2);
+    rooms[1].set_object(0, 0, RoomObject::TreasureChest);
+    rooms[2].set_object(0, 0, RoomObject::TreasureChest);
+    rooms[2].set_object(1, 0, RoomObject::TreasureChest);
+    rooms[2].set_object(2, 0, RoomObject::MONSTER_SPAWN);
+    rooms[2].set_object(3, 0, RoomObject::MONSTER_SPAWN);
+    rooms[2].monster_id = 3;
+    rooms[2].monster_count = 4;
+    rooms[2].set_tile(5, 5, Tile::from_byte(b'U'));
+    rooms[3].set_tile(5, 5, Tile::Graphic(21));
+
+    let stats = statistics(&rooms, 0);
+    assert_eq!(stats.rooms, 4);
+    assert_eq!(stats.links, 4);
+    assert_eq!(stats.average_links(), 1.0);
+    assert_eq!(stats.rooms_without_exits, vec![3]);
+    assert_eq!(stats.spawns, 2);
+    assert_eq!(stats.tiles[&0], 4 * 160 - 2);
+    assert_eq!(stats.tiles[&21], 1);
+    assert_eq!(stats.traps.values().sum::<usize>(), 1);
+    assert_eq!(stats.objects[&b'e'], 3);
+    assert_eq!(stats.monsters[&3].rooms, 1);
+    assert_eq!(stats.monsters[&3].spawns, 2);
+    assert_eq!(stats.monsters[&3].count, 4);
+    assert_eq!(stats.floors[&Some(0)].rooms, 2);
+    assert_eq!(stats.floors[&Some(0)].treasure_chests, 1);
+    assert_eq!(stats.floors[&Some(1)].treasure_chests, 2);
+    assert_eq!(stats.floors[&Some(1)].traps, 1);
+    assert_eq!(stats.floors[&None].rooms, 1);
+
+    let csv = stats.to_csv();
+    assert!(csv.starts_with("statistic,key,value\n"));
+    assert!(csv.contains("\nfloor treasure chests,2,2\n"));
+    assert!(csv.contains("\nobject,Treasure chest,3\n"));
+    assert!(csv.contains("\nfloor rooms,unreachable,1\n"));
+}
+
+#[test]
+fn route_test() {
+    use super::graph::{describe_route, route};
+    use super::rms::{link_rooms, Direction};
+
+    let mut rooms = unlinked_rooms(6);
+    link_rooms(&mut rooms, 0, Direction::East, 1);
+    link_rooms(&mut rooms, 1, Direction::East, 2);
+    link_rooms(&mut rooms, 2, Direction::Down, 3);
+    link_rooms(&mut rooms, 3, Direction::South, 4);
+    // A shortcut from the entrance straight down
+    link_rooms(&mut rooms, 0, Direction::Down, 3);
+
+    let moves = route(&rooms, 0, 4).unwrap();
+    assert_eq!(moves, vec![(Direction::Down, 3), (Direction::South, 4)]);
+    assert_eq!(describe_route(&moves), "Down, S");
+    // The shortcut replaced room 4's ladder up, so the way back is through the entrance
+    let back = route(&rooms, 4, 1).unwrap();
+    assert_eq!(describe_route(&back), "N, Up, E");
+    assert_eq!(route(&rooms, 2, 2), Some(vec![]));
+    assert_eq!(route(&rooms, 0, 5), None);
+    assert_eq!(route(&rooms, 0, 9), None);
+}
+
+#[test]
+fn walkability_test() {
+    use super::object::RoomObject;
+    use super::rms::{link_rooms, Direction, Tile, ROOM_HEIGHT, ROOM_WIDTH};
+    use super::validate::{validate_walkability, Field};
+    use super::walk::{optimistic_walkability, walkability, Passability};
+
+    let mut rooms = unlinked_rooms(5);
+    for room in rooms.iter_mut() {
+        for y in 0..ROOM_HEIGHT {
+            for x in 0..ROOM_WIDTH {
+                room.set_tile(x, y, Tile::Graphic(1));
+                room.set_object(x, y, RoomObject::None);
+            }
+        }
+        room.monster_id = 0;
+        room.monster_count = 0;
+    }
+    // A corridor from west to east, blocked by a soft wall that can be broken through
+    for x in 0..ROOM_WIDTH {
+        rooms[0].set_tile(x, 3, Tile::Graphic(21));
+    }
+    rooms[0].set_object(10, 3, RoomObject::SoftSectionOfWall);
+    rooms[0].set_tile(11, 3, Tile::from_byte(b'U'));
+    // A sealed pocket with a chest in it, and a coffin in an alcove off the corridor
+    rooms[0].set_tile(5, 6, Tile::Graphic(21));
+    rooms[0].set_object(5, 6, RoomObject::TreasureChest);
+    rooms[0].set_object(7, 2, RoomObject::OldStoneCoffin);
+    // A corridor to the south that doesn't meet the other one
+    for y in 5..ROOM_HEIGHT {
+        rooms[0].set_tile(15, y, Tile::Graphic(21));
+    }
+    link_rooms(&mut rooms, 0, Direction::West, 1);
+    link_rooms(&mut rooms, 0, Direction::East, 2);
+    link_rooms(&mut rooms, 0, Direction::South, 3);
+    link_rooms(&mut rooms, 0, Direction::North, 4);
+
+    assert_eq!(
+        Passability::of_tile(Tile::from_byte(b'U')),
+        Passability::Floor
+    );
+    let walk = walkability(&rooms[0]);
+    assert!(walk.connects(Direction::West, Direction::East));
+    assert!(!walk.connects(Direction::West, Direction::South));
+    assert!(walk.openings(Direction::North).is_empty());
+    assert_eq!(walk.openings(Direction::South), vec![(15, 7)]);
+    assert!(walk.is_reachable(12, 3));
+    assert!(walk.is_reachable(15, 5));
+    assert!(!walk.is_reachable(5, 6));
+    assert!(!walk.is_reachable(7, 2));
+    assert_eq!(walk.unreachable_spots, vec![(5, 6)]);
+
+    let issues = validate_walkability(&rooms);
+    let fields: Vec<(usize, Field)> = issues
+        .iter()
+        .map(|issue| (issue.room, issue.field))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            (0, Field::Nav(Direction::North)),
+            (0, Field::Nav(Direction::South)),
+            (0, Field::Room),
+            // Walls on every side
+            (1, Field::Nav(Direction::East)),
+            (2, Field::Nav(Direction::West)),
+            (3, Field::Nav(Direction::North)),
+            (4, Field::Nav(Direction::South)),
+        ]
+    );
+    assert_eq!(
+        issues[1].message,
+        "probably can't be walked to from the east exit"
+    );
+
+    // Tiles nobody has worked out block the way for placing things, but don't cause warnings
+    assert_eq!(Passability::of_tile(Tile::Graphic(2)), Passability::Unknown);
+    for y in 0..ROOM_HEIGHT {
+        for x in 0..ROOM_WIDTH {
+            rooms[1].set_tile(x, y, Tile::Graphic(2));
+        }
+    }
+    assert!(walkability(&rooms[1]).openings(Direction::East).is_empty());
+    assert!(!optimistic_walkability(&rooms[1])
+        .openings(Direction::East)
+        .is_empty());
+    assert_eq!(validate_walkability(&rooms[1..2]), vec![]);
+}
+
+#[test]
+fn edge_test() {
+    use super::object::RoomObject;
+    use super::rms::{link_rooms, Direction, Tile, ROOM_HEIGHT, ROOM_WIDTH};
+    use super::validate::validate_edges;
+    use super::walk::edge_mismatches;
+
+    let mut rooms = unlinked_rooms(4);
+    for room in rooms.iter_mut() {
+        for y in 0..ROOM_HEIGHT {
+            for x in 0..ROOM_WIDTH {
+                room.set_tile(x, y, Tile::Graphic(1));
+                room.set_object(x, y, RoomObject::None);
+            }
+        }
+    }
+    rooms[0].set_tile(3, 0, Tile::Graphic(21));
+    rooms[0].set_tile(4, 0, Tile::Graphic(21));
+    rooms[1].set_tile(4, ROOM_HEIGHT - 1, Tile::Graphic(21));
+    rooms[1].set_tile(5, ROOM_HEIGHT - 1, Tile::Graphic(21));
+    link_rooms(&mut rooms, 0, Direction::North, 1);
+    // These line up
+    rooms[0].set_tile(ROOM_WIDTH - 1, 2, Tile::Graphic(21));
+    rooms[2].set_tile(0, 2, Tile::Graphic(21));
+    link_rooms(&mut rooms, 0, Direction::East, 2);
+    link_rooms(&mut rooms, 0, Direction::Down, 3);
+
+    assert_eq!(
+        edge_mismatches(&rooms, 0, Direction::North),
+        Some(vec![3, 5])
+    );
+    assert_eq!(
+        edge_mismatches(&rooms, 1, Direction::South),
+        Some(vec![3, 5])
+    );
+    assert_eq!(edge_mismatches(&rooms, 0, Direction::East), Some(vec![]));
+    assert_eq!(edge_mismatches(&rooms, 0, Direction::West), None);
+    assert_eq!(edge_mismatches(&rooms, 0, Direction::Down), None);
+
+    let issues = validate_edges(&rooms);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].room, 0);
+    assert_eq!(
+        issues[0].message,
+        "doesn't line up with room 2's south edge in columns 3, 5"
+    );
+    // Only one way, so only that room says so
+    rooms[0].set_link(Direction::North, None);
+    let issues = validate_edges(&rooms);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].room, 1);
+}
+
+#[test]
+fn generate_test() {
+    use super::generate::{generate, Parameters};
+    use super::layout::layout;
+    use super::monster::Monster;
+    use super::object::RoomObject;
+    use super::rms_text::room_to_text;
+    use super::validate;
+
+    let monsters: Vec<Monster> = (1..=10).map(|gfx_id| Monster { gfx_id }).collect();
+    let parameters = Parameters {
+        floors: 4,
+        rooms_per_floor: 15,
+        loop_density: 0.5,
+        monster_curve: 1.0,
+        treasure_density: 1.5,
+        seed: 1990,
+    };
+    let rooms = generate(&parameters, monsters.len()).unwrap();
+    assert_eq!(rooms.len(), 60);
+    assert_eq!(validate::validate(&rooms), vec![]);
+    assert_eq!(validate::validate_walkability(&rooms), vec![]);
+    assert_eq!(validate::validate_edges(&rooms), vec![]);
+    assert_eq!(validate::validate_monsters(&rooms, &monsters), vec![]);
+    assert!(layout(&rooms, validate::STARTING_ROOM)
+        .contradictions
+        .is_empty());
+    assert_eq!(rooms[0].monster_id, 0);
+    let chests: usize = rooms
+        .iter()
+        .map(|room| {
+            (0..super::rms::ROOM_HEIGHT)
+                .flat_map(|y| (0..super::rms::ROOM_WIDTH).map(move |x| (x, y)))
+                .filter(|&(x, y)| room.get_object(x, y) == RoomObject::TreasureChest)
+                .count()
+        })
+        .sum();
+    assert!((60..=120).contains(&chests));
+    // Monsters get tougher going down
+    let toughness = |floor: usize| -> u32 {
+        rooms[floor * 15..(floor + 1) * 15]
+            .iter()
+            .map(|room| room.monster_id as u32)
+            .sum()
+    };
+    assert!(toughness(0) < toughness(1));
+    assert!(toughness(1) < toughness(2));
+    assert!(toughness(2) < toughness(3));
+
+    // The same seed makes the same dungeon
+    let again = generate(&parameters, monsters.len()).unwrap();
+    let text = |rooms: &[super::rms::Room]| -> Vec<String> {
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(i, room)| room_to_text(i + 1, room))
+            .collect()
+    };
+    assert_eq!(text(&rooms), text(&again));
+    let other = Parameters {
+        seed: 1991,
+        ..parameters.clone()
+    };
+    assert_ne!(
+        text(&rooms),
+        text(&generate(&other, monsters.len()).unwrap())
+    );
+
+    // Whatever the seed
+    for seed in 0..20 {
+        let rooms = generate(
+            &Parameters {
+                seed,
+                ..Parameters::default()
+            },
+            monsters.len(),
+        )
+        .unwrap();
+        assert_eq!(validate::validate(&rooms), vec![]);
+        assert_eq!(validate::validate_walkability(&rooms), vec![]);
+        assert_eq!(validate::validate_edges(&rooms), vec![]);
+    }
+
+    let too_many = Parameters {
+        floors: 20,
+        ..parameters
+    };
+    assert!(generate(&too_many, monsters.len()).is_err());
+}
+
+#[test]
+fn randomize_test() {
+    use super::generate::{generate, Parameters};
+    use super::graph::floors_from;
+    use super::object::RoomObject;
+    use super::randomize::{floor_difficulty, randomize, Rules};
+    use super::rms::{Room, ROOM_HEIGHT, ROOM_WIDTH};
+    use super::validate;
+    use super::walk;
+
+    let mut original = generate(&Parameters::default(), 20).unwrap();
+    original[3].set_object(5, 3, RoomObject::OldBones);
+    original[3].set_object(6, 3, RoomObject::OldGrave);
+    let count = |room: &Room, wanted: fn(RoomObject) -> bool| {
+        (0..ROOM_HEIGHT)
+            .flat_map(|y| (0..ROOM_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| wanted(room.get_object(x, y)))
+            .count()
+    };
+    let is_chest = |object| object == RoomObject::TreasureChest;
+    let is_spawn = |object| matches!(object, RoomObject::Monster(_));
+    let is_other = |object| !matches!(object, RoomObject::None | RoomObject::Monster(_));
+
+    let rules = Rules {
+        tolerance: 1.5,
+        seed: 7,
+        ..Rules::default()
+    };
+    let mut rooms = original.clone();
+    let shuffled = randomize(&mut rooms, &rules);
+    assert!(shuffled.monsters > 0);
+    assert_eq!(shuffled.objects, 2);
+
+    let floors = floors_from(&original, validate::STARTING_ROOM);
+    let mut chests_before = std::collections::BTreeMap::new();
+    let mut chests_after = std::collections::BTreeMap::new();
+    for (i, (room, before)) in rooms.iter().zip(original.iter()).enumerate() {
+        // Layouts stay put, and nothing lands somewhere it can't be reached
+        for y in 0..ROOM_HEIGHT {
+            for x in 0..ROOM_WIDTH {
+                assert_eq!(room.get_tile(x, y), before.get_tile(x, y));
+            }
+        }
+        assert_eq!(walk::walkability(room).unreachable_spots, vec![]);
+        assert_eq!(count(room, is_spawn), count(before, is_spawn));
+        assert_eq!(
+            count(room, is_other) - count(room, is_chest),
+            count(before, is_other) - count(before, is_chest)
+        );
+        *chests_before.entry(floors[i]).or_insert(0) += count(before, is_chest);
+        *chests_after.entry(floors[i]).or_insert(0) += count(room, is_chest);
+        assert_eq!(room.monster_count, before.monster_count);
+    }
+    assert_eq!(chests_before, chests_after);
+    assert_eq!(validate::validate(&rooms), vec![]);
+    assert_eq!(validate::validate_walkability(&rooms), vec![]);
+
+    let before = floor_difficulty(&original);
+    let after = floor_difficulty(&rooms);
+    for (floor, average) in before.iter() {
+        assert!((after[floor] - average).abs() <= 1.5);
+    }
+
+    // The same seed shuffles the same way
+    let mut again = original.clone();
+    assert_eq!(randomize(&mut again, &rules), shuffled);
+    let text = |rooms: &[Room]| -> Vec<String> {
+        rooms
+            .iter()
+            .enumerate()
+            .map(|(i, room)| super::rms_text::room_to_text(i + 1, room))
+            .collect()
+    };
+    assert_eq!(text(&rooms), text(&again));
+    assert_ne!(text(&rooms), text(&original));
+
+    // Keeping everything changes nothing
+    let mut kept = original.clone();
+    let nothing = Rules {
+        monsters: false,
+        spawns: false,
+        chests: false,
+        objects: false,
+        ..rules
+    };
+    randomize(&mut kept, &nothing);
+    assert_eq!(text(&kept), text(&original));
 }
@@ -1,9 +1,16 @@
 use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
 use sdl2::render::Texture;
 use sdl2::render::TextureCreator;
 use sdl2::ttf::Font;
 
+use super::img;
+
+/// The font everything is drawn with, relative to the game directory.
+pub const FONT_FILENAME: &str = "fonts/liberation-fonts-ttf-2.1.2/LiberationSans-Regular.ttf";
+pub const FONT_SIZE: u16 = 16;
+
 /// Stores results for rendering text to a drawable texture with conveniences for copying to a
 /// canvas.
 ///
@@ -155,3 +162,40 @@ impl<'r, CanvasT> TextLabel<'r, CanvasT> {
         self.rendering.texture()
     }
 }
+
+/// Render text to an image instead of a texture, for drawing without a window. Empty text is an
+/// empty image.
+pub fn render_image(text: &str, color: img::Color, font: &Font) -> img::Image {
+    if text.is_empty() {
+        return img::Image {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        };
+    }
+    let surface = font
+        .render(text)
+        .blended(Color::RGBA(color.r, color.g, color.b, color.a))
+        .unwrap()
+        .convert_format(PixelFormatEnum::RGBA32)
+        .unwrap();
+    let width = surface.width() as usize;
+    let height = surface.height() as usize;
+    let pitch = surface.pitch() as usize;
+    let pixels = surface.with_lock(|bytes| {
+        (0..height)
+            .flat_map(|y| bytes[y * pitch..y * pitch + width * 4].chunks(4))
+            .map(|rgba| img::Color {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            })
+            .collect()
+    });
+    img::Image {
+        width,
+        height,
+        pixels,
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+use super::graph;
+use super::monster::Monster;
+use super::rms::{Direction, Room, RoomIndex};
+use super::walk;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Severity {
+    /// Probably a mistake, but the game can still run with it.
+    Warning,
+    /// The game will misbehave or crash.
+    Error,
+}
+
+/// The part of a room record that an Issue is about.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Field {
+    /// The room as a whole
+    Room,
+    Name,
+    Nav(Direction),
+    Id,
+    MonsterId,
+}
+
+/// A single problem found by validate().
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub room: RoomIndex,
+    pub field: Field,
+    pub message: String,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Room => write!(f, "room"),
+            Field::Name => write!(f, "name"),
+            Field::Nav(direction) => write!(f, "{} exit", direction.name()),
+            Field::Id => write!(f, "id"),
+            Field::MonsterId => write!(f, "monster"),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Room numbers are shown 1-based to match the game
+        write!(
+            f,
+            "{}: room {}: {}: {}",
+            self.severity,
+            self.room + 1,
+            self.field,
+            self.message
+        )
+    }
+}
+
+/// The room the player starts in.
+pub const STARTING_ROOM: RoomIndex = 0;
+
+/// Check the dungeon for broken links and bookkeeping mistakes. Issues are ordered by room.
+pub fn validate(rooms: &[Room]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for (index, room) in rooms.iter().enumerate() {
+        if room.id() as RoomIndex != index + 1 {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                room: index,
+                field: Field::Id,
+                message: format!("id is {} but the room is number {}", room.id(), index + 1),
+            });
+        }
+
+        if let Err(e) = room.check_name() {
+            issues.push(Issue {
+                severity: Severity::Error,
+                room: index,
+                field: Field::Name,
+                message: format!("can't be saved: {}", e),
+            });
+        }
+
+        for &direction in Direction::ALL.iter() {
+            let target = match room.link(direction) {
+                Some(target) => target,
+                None => continue,
+            };
+            let field = Field::Nav(direction);
+            if target >= rooms.len() {
+                issues.push(Issue {
+                    severity: Severity::Error,
+                    room: index,
+                    field,
+                    message: format!(
+                        "leads to room {} but there are only {} rooms",
+                        target + 1,
+                        rooms.len()
+                    ),
+                });
+            } else if target == index {
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    room: index,
+                    field,
+                    message: String::from("leads back into the same room"),
+                });
+            } else if rooms[target].link(direction.opposite()) != Some(index) {
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    room: index,
+                    field,
+                    message: format!(
+                        "leads to room {} whose {} exit doesn't lead back",
+                        target + 1,
+                        direction.opposite().name()
+                    ),
+                });
+            }
+        }
+    }
+
+    let reachable = reachable_rooms(rooms, STARTING_ROOM);
+    for (index, _) in reachable.iter().enumerate().filter(|(_, &r)| !r) {
+        issues.push(Issue {
+            severity: Severity::Warning,
+            room: index,
+            field: Field::Room,
+            message: format!("can't be reached from room {}", STARTING_ROOM + 1),
+        });
+    }
+
+    // Keep each room's issues together
+    issues.sort_by_key(|issue| issue.room);
+    issues
+}
+
+/// Check that every room's monster is in the monster list. This is separate from validate() since
+/// the monster list lives in a different file.
+pub fn validate_monsters(rooms: &[Room], monsters: &[Monster]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, room) in rooms.iter().enumerate() {
+        let spawns = room.monster_spawns().len();
+        match room.monster_index() {
+            Some(monster) if monster >= monsters.len() => issues.push(Issue {
+                severity: Severity::Error,
+                room: index,
+                field: Field::MonsterId,
+                message: format!(
+                    "is {} but there are only {} monsters",
+                    room.monster_id,
+                    monsters.len()
+                ),
+            }),
+            None if spawns > 0 || room.monster_count > 0 => issues.push(Issue {
+                severity: Severity::Error,
+                room: index,
+                field: Field::MonsterId,
+                message: format!(
+                    "is not set but the room has {} monsters and {} spawn points",
+                    room.monster_count, spawns
+                ),
+            }),
+            _ => {}
+        }
+    }
+    issues
+}
+
+/// Check that the player can get from each edge exit of a room to the others, and to every
+/// monster and object worth searching. Separate from validate() since it's only as good as
+/// walk::Passability's idea of which tiles are walls, which is a guess, so the warnings say
+/// "probably". Tiles nobody has worked out are taken to be floor so they don't cause warnings.
+pub fn validate_walkability(rooms: &[Room]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, room) in rooms.iter().enumerate() {
+        let walkability = walk::optimistic_walkability(room);
+        let mut first_open_exit = None;
+        for &direction in Direction::ALL[..4].iter() {
+            if room.link(direction).is_none() {
+                continue;
+            }
+            if walkability.openings(direction).is_empty() {
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    room: index,
+                    field: Field::Nav(direction),
+                    message: String::from("is probably walled off"),
+                });
+                continue;
+            }
+            match first_open_exit {
+                None => first_open_exit = Some(direction),
+                Some(first) if !walkability.connects(first, direction) => issues.push(Issue {
+                    severity: Severity::Warning,
+                    room: index,
+                    field: Field::Nav(direction),
+                    message: format!("probably can't be walked to from the {} exit", first.name()),
+                }),
+                Some(_) => {}
+            }
+        }
+        for &(x, y) in walkability.unreachable_spots.iter() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                room: index,
+                field: Field::Room,
+                message: format!(
+                    "{} at {},{} probably can't be reached",
+                    room.get_object(x, y).name(),
+                    x,
+                    y
+                ),
+            });
+        }
+    }
+    issues
+}
+
+/// Check that the openings on each edge line up with the facing edge of the room it leads to, so
+/// the player doesn't walk out of a corridor into a wall.
+pub fn validate_edges(rooms: &[Room]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for index in 0..rooms.len() {
+        for &direction in Direction::ALL[..4].iter() {
+            let mismatches = match walk::edge_mismatches(rooms, index, direction) {
+                Some(mismatches) if !mismatches.is_empty() => mismatches,
+                _ => continue,
+            };
+            let target = rooms[index].link(direction).unwrap();
+            // A two way link is reported once, from the first room
+            if target < index && rooms[target].link(direction.opposite()) == Some(index) {
+                continue;
+            }
+            let along = match direction {
+                Direction::North | Direction::South => "column",
+                _ => "row",
+            };
+            let mismatches: Vec<String> = mismatches.iter().map(u32::to_string).collect();
+            issues.push(Issue {
+                severity: Severity::Warning,
+                room: index,
+                field: Field::Nav(direction),
+                message: format!(
+                    "doesn't line up with room {}'s {} edge in {}{} {}",
+                    target + 1,
+                    direction.opposite().name(),
+                    along,
+                    if mismatches.len() == 1 { "" } else { "s" },
+                    mismatches.join(", ")
+                ),
+            });
+        }
+    }
+    issues
+}
+
+/// Which rooms can be walked to from `start`, indexed by room. Links past the end of the room
+/// list are ignored.
+pub fn reachable_rooms(rooms: &[Room], start: RoomIndex) -> Vec<bool> {
+    graph::distances_from(rooms, start)
+        .iter()
+        .map(Option::is_some)
+        .collect()
+}
+
+/// One line summary of the issues for a single room, for showing in the editor.
+pub fn summarize(issues: &[Issue], room: RoomIndex) -> String {
+    let mut room_issues = issues.iter().filter(|issue| issue.room == room);
+    match (room_issues.next(), room_issues.count()) {
+        (None, _) => String::from("No problems"),
+        (Some(issue), 0) => format!("{}: {}", issue.field, issue.message),
+        (Some(issue), more) => format!("{}: {} (+{} more)", issue.field, issue.message, more),
+    }
+}
+
+/// Convenience for callers that only care whether the dungeon is usable.
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
@@ -0,0 +1,312 @@
+use std::collections::VecDeque;
+
+use super::object::RoomObject;
+use super::rms::{Direction, Room, RoomIndex, Tile, ROOM_HEIGHT, ROOM_WIDTH};
+
+/// How a cell treats the player walking into it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Passability {
+    Wall,
+    Floor,
+    Door,
+    Water,
+    SoftWall,
+    MovableBlock,
+    /// A tile nobody has worked out yet, which could be any of the others
+    Unknown,
+}
+
+/// The tiles we know something about. The open ones are the graphics RoomObject::sprite() draws
+/// for objects the player walks over or through, plus 21, the floor traps are drawn on, and 0.
+/// 1 is the wall generate builds rooms from. Every other tile is Unknown, and anything built on
+/// this (see validate::validate_walkability()) is a guess.
+/// TODO: Nobody has worked out which tiles are water yet, so none are listed
+const KNOWN_TILES: [(u8, Passability); 12] = [
+    (0, Passability::Floor), // Nothing drawn, so nothing in the way
+    (1, Passability::Wall),
+    (18, Passability::Floor),    // Old bones
+    (19, Passability::SoftWall), // Soft pile of rubble
+    (21, Passability::Floor),
+    (22, Passability::Floor), // Treasure chest
+    (23, Passability::Floor), // Old body
+    (30, Passability::MovableBlock),
+    (37, Passability::Door),
+    (38, Passability::Door),
+    (43, Passability::SoftWall), // Soft piece of wall
+    (83, Passability::Floor),    // Some old blood
+];
+
+impl Passability {
+    pub const ALL: [Passability; 7] = [
+        Passability::Wall,
+        Passability::Floor,
+        Passability::Door,
+        Passability::Water,
+        Passability::SoftWall,
+        Passability::MovableBlock,
+        Passability::Unknown,
+    ];
+
+    pub fn of_tile(tile: Tile) -> Passability {
+        match tile {
+            // Traps look like floor and are walked onto, that's the point of them
+            Tile::Trap(_) => Passability::Floor,
+            Tile::Graphic(graphic) => KNOWN_TILES
+                .iter()
+                .find(|&&(known, _)| known == graphic)
+                .map_or(Passability::Unknown, |&(_, passability)| passability),
+        }
+    }
+
+    /// Objects that block the way or open one. None for objects that leave the tile under them
+    /// to decide.
+    pub fn of_object(object: RoomObject) -> Option<Passability> {
+        match object {
+            RoomObject::DoorVertical | RoomObject::DoorHorizontal => Some(Passability::Door),
+            RoomObject::SoftSectionOfWall
+            | RoomObject::SoftPieceOfWall
+            | RoomObject::SoftPileOfRubble => Some(Passability::SoftWall),
+            RoomObject::MovableBlock | RoomObject::MovableGlassBlock => {
+                Some(Passability::MovableBlock)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the player can get through. Soft walls can be broken and blocks pushed out of the
+    /// way, so neither one traps the player. Unknown tiles aren't, which is the safe guess when
+    /// putting things in a room; see optimistic_walkability() for the other guess.
+    pub fn is_passable(self) -> bool {
+        !matches!(
+            self,
+            Passability::Wall | Passability::Water | Passability::Unknown
+        )
+    }
+
+    /// How the cell is shown when a room is printed as text.
+    pub fn character(self) -> char {
+        match self {
+            Passability::Wall => '#',
+            Passability::Floor => '.',
+            Passability::Door => '+',
+            Passability::Water => '~',
+            Passability::SoftWall => '%',
+            Passability::MovableBlock => 'B',
+            Passability::Unknown => '?',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Passability::Wall => "wall",
+            Passability::Floor => "floor",
+            Passability::Door => "door",
+            Passability::Water => "water",
+            Passability::SoftWall => "soft wall",
+            Passability::MovableBlock => "movable block",
+            Passability::Unknown => "unknown tile",
+        }
+    }
+}
+
+/// The object decides if it has an opinion, otherwise the tile does.
+pub fn cell_passability(room: &Room, x: u32, y: u32) -> Passability {
+    Passability::of_object(room.get_object(x, y))
+        .unwrap_or_else(|| Passability::of_tile(room.get_tile(x, y)))
+}
+
+/// Objects the player needs to get to: monsters to fight and things to search.
+pub fn is_spot(object: RoomObject) -> bool {
+    matches!(
+        object,
+        RoomObject::Monster(_)
+            | RoomObject::TreasureChest
+            | RoomObject::FunnyLookingChest
+            | RoomObject::OldBody
+            | RoomObject::OldBones
+            | RoomObject::OldStoneCoffin
+            | RoomObject::OldGrave
+            | RoomObject::OldSkeleton
+            | RoomObject::OldSkeletonAlt
+            | RoomObject::HollowObelisk
+            | RoomObject::StoneMarker
+    )
+}
+
+/// The cells along the edge the player walks off to take an exit. Ladders aren't tied to a cell
+/// (as far as we know) so Up and Down have none.
+pub fn edge_cells(direction: Direction) -> Vec<(u32, u32)> {
+    match direction {
+        Direction::North => (0..ROOM_WIDTH).map(|x| (x, 0)).collect(),
+        Direction::South => (0..ROOM_WIDTH).map(|x| (x, ROOM_HEIGHT - 1)).collect(),
+        Direction::West => (0..ROOM_HEIGHT).map(|y| (0, y)).collect(),
+        Direction::East => (0..ROOM_HEIGHT).map(|y| (ROOM_WIDTH - 1, y)).collect(),
+        Direction::Up | Direction::Down => Vec::new(),
+    }
+}
+
+fn edge_passabilities(room: &Room, direction: Direction) -> Vec<Passability> {
+    edge_cells(direction)
+        .into_iter()
+        .map(|(x, y)| cell_passability(room, x, y))
+        .collect()
+}
+
+/// Whether each cell along an edge is passable, in the same order as edge_cells().
+pub fn edge_openings(room: &Room, direction: Direction) -> Vec<bool> {
+    edge_passabilities(room, direction)
+        .into_iter()
+        .map(Passability::is_passable)
+        .collect()
+}
+
+/// Where the edge of `rooms[index]` in `direction` doesn't line up with the facing edge of the
+/// room it leads to: columns for north and south, rows for east and west. Unknown tiles on either
+/// side could be anything, so they're left out. None if there's no room on that side to compare
+/// with.
+pub fn edge_mismatches(rooms: &[Room], index: RoomIndex, direction: Direction) -> Option<Vec<u32>> {
+    let target = rooms[index]
+        .link(direction)
+        .filter(|&target| target < rooms.len() && target != index)?;
+    if edge_cells(direction).is_empty() {
+        return None;
+    }
+    let here = edge_passabilities(&rooms[index], direction);
+    let there = edge_passabilities(&rooms[target], direction.opposite());
+    Some(
+        (0..here.len())
+            .filter(|&i| {
+                here[i] != Passability::Unknown
+                    && there[i] != Passability::Unknown
+                    && here[i].is_passable() != there[i].is_passable()
+            })
+            .map(|i| i as u32)
+            .collect(),
+    )
+}
+
+fn neighbours(x: u32, y: u32) -> impl Iterator<Item = (u32, u32)> {
+    let (x, y) = (x as i32, y as i32);
+    vec![(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)]
+        .into_iter()
+        .filter(|&(x, y)| x >= 0 && y >= 0 && x < ROOM_WIDTH as i32 && y < ROOM_HEIGHT as i32)
+        .map(|(x, y)| (x as u32, y as u32))
+}
+
+/// Where the player can walk inside one room.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Walkability {
+    /// Which connected area each passable cell belongs to, by y * ROOM_WIDTH + x
+    areas: Vec<Option<usize>>,
+    /// By area, whether the player can get into it through one of the room's exits
+    entered: Vec<bool>,
+    /// Monsters and objects to search that can't be reached, see is_spot()
+    pub unreachable_spots: Vec<(u32, u32)>,
+}
+
+impl Walkability {
+    pub fn area(&self, x: u32, y: u32) -> Option<usize> {
+        self.areas[(y * ROOM_WIDTH + x) as usize]
+    }
+
+    /// The passable cells on the exit's edge.
+    pub fn openings(&self, direction: Direction) -> Vec<(u32, u32)> {
+        edge_cells(direction)
+            .into_iter()
+            .filter(|&(x, y)| self.area(x, y).is_some())
+            .collect()
+    }
+
+    /// Whether the player can walk from one exit to the other without leaving the room.
+    pub fn connects(&self, from: Direction, to: Direction) -> bool {
+        let to_areas: Vec<usize> = self
+            .openings(to)
+            .iter()
+            .filter_map(|&(x, y)| self.area(x, y))
+            .collect();
+        self.openings(from)
+            .iter()
+            .any(|&(x, y)| self.area(x, y).is_some_and(|area| to_areas.contains(&area)))
+    }
+
+    /// Whether the player can walk onto the cell after coming in through an exit.
+    pub fn is_reachable(&self, x: u32, y: u32) -> bool {
+        self.area(x, y).is_some_and(|area| self.entered[area])
+    }
+}
+
+/// Work out where the player can walk in a room. The player comes in through the linked exits on
+/// the room's edges. A room that can only be entered by ladder could put the player anywhere, so
+/// then every passable cell counts as reachable. Unknown tiles are taken to be walls.
+pub fn walkability(room: &Room) -> Walkability {
+    walkability_where(room, Passability::is_passable)
+}
+
+/// Like walkability(), but with unknown tiles taken to be floor. Whatever this can't reach can't
+/// be reached however those tiles turn out, so it's the one to warn about problems with.
+pub fn optimistic_walkability(room: &Room) -> Walkability {
+    walkability_where(room, |passability| {
+        passability == Passability::Unknown || passability.is_passable()
+    })
+}
+
+fn walkability_where(room: &Room, is_passable: impl Fn(Passability) -> bool) -> Walkability {
+    let mut areas = vec![None; (ROOM_WIDTH * ROOM_HEIGHT) as usize];
+    let mut area_count = 0;
+    for y in 0..ROOM_HEIGHT {
+        for x in 0..ROOM_WIDTH {
+            if areas[(y * ROOM_WIDTH + x) as usize].is_some()
+                || !is_passable(cell_passability(room, x, y))
+            {
+                continue;
+            }
+            // Flood out from the first cell of each new area
+            let mut queue = VecDeque::new();
+            areas[(y * ROOM_WIDTH + x) as usize] = Some(area_count);
+            queue.push_back((x, y));
+            while let Some((x, y)) = queue.pop_front() {
+                for (x, y) in neighbours(x, y) {
+                    let area = &mut areas[(y * ROOM_WIDTH + x) as usize];
+                    if area.is_none() && is_passable(cell_passability(room, x, y)) {
+                        *area = Some(area_count);
+                        queue.push_back((x, y));
+                    }
+                }
+            }
+            area_count += 1;
+        }
+    }
+
+    let mut walkability = Walkability {
+        areas,
+        entered: vec![false; area_count],
+        unreachable_spots: Vec::new(),
+    };
+    let edge_exits: Vec<Direction> = Direction::ALL[..4]
+        .iter()
+        .copied()
+        .filter(|&direction| room.link(direction).is_some())
+        .collect();
+    if edge_exits.is_empty() {
+        walkability.entered = vec![true; area_count];
+    }
+    for direction in edge_exits {
+        for (x, y) in walkability.openings(direction) {
+            if let Some(area) = walkability.area(x, y) {
+                walkability.entered[area] = true;
+            }
+        }
+    }
+
+    for y in 0..ROOM_HEIGHT {
+        for x in 0..ROOM_WIDTH {
+            // Things in the way (a coffin in an alcove) only need to be stood next to
+            let reachable = walkability.is_reachable(x, y)
+                || neighbours(x, y).any(|(x, y)| walkability.is_reachable(x, y));
+            if is_spot(room.get_object(x, y)) && !reachable {
+                walkability.unreachable_spots.push((x, y));
+            }
+        }
+    }
+    walkability
+}